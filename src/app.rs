@@ -1,32 +1,61 @@
+use crate::dialogs::{ActionDialog, RepoAction};
+use crate::loader::{copy_settings, GraphLoader};
 use crate::settings::AppSettings;
-use crate::util::syntax_highlight::highlight;
+use crate::util::clipboard::copy_to_clipboard;
+use crate::util::diff::{split_lines, SplitLine};
+use crate::util::export::{export_graph, ExportFormat};
+use crate::util::external::{self, ExternalCommand};
+use crate::util::filter::{CommitFilter, PathHistory};
+use crate::util::git;
+use crate::util::search::fold_case;
+use crate::util::syntax_highlight::{highlight, HighlightCache};
+use crate::watcher::RepoWatcher;
 use crate::widgets::branches_view::{BranchItem, BranchItemType};
 use crate::widgets::commit_view::{CommitViewInfo, CommitViewState, DiffItem};
-use crate::widgets::diff_view::{DiffViewInfo, DiffViewState};
-use crate::widgets::graph_view::GraphViewState;
-use crate::widgets::list::StatefulList;
+use crate::widgets::diff_view::{BlameInfo, DiffViewInfo, DiffViewState};
+use crate::widgets::graph_view::{GraphViewState, PseudoCommit};
+use crate::widgets::list::{ListItem, StatefulList};
 use crate::widgets::models_view::ModelListState;
-use git2::{Commit, DiffDelta, DiffFormat, DiffHunk, DiffLine, DiffOptions as GDiffOptions, Oid};
+use crate::widgets::reflog_view::{ReflogItem, ReflogState};
+use git2::{
+    BlameOptions, Delta, Diff, DiffDelta, DiffFormat, DiffHunk, DiffLine,
+    DiffOptions as GDiffOptions, ObjectType, Oid, Repository, Status, StatusOptions,
+};
 use git_graph::config::get_available_models;
 use git_graph::graph::GitGraph;
-use git_graph::print::unicode::{format_branches, print_unicode};
+use git_graph::print::format::format_date;
+use git_graph::print::unicode::format_branches;
 use git_graph::settings::Settings;
+use serde::{Deserialize, Serialize};
 use std::fmt::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::str::FromStr;
-use tui::style::Color;
-
-const HASH_COLOR: u8 = 11;
-
-#[derive(PartialEq, Eq)]
+use std::time::{Duration, Instant};
+use syntect::highlighting::Style as SynStyle;
+use tui::layout::{Direction, Rect};
+
+const PSEUDO_COMMIT_CHAR: &str = "\u{25CC}";
+const BLAME_AUTHOR_WIDTH: usize = 12;
+const MOUSE_SCROLL_LINES: usize = 3;
+const MIN_SPLIT_PERCENT: u16 = 10;
+/// Time a status message is shown
+const STATUS_DURATION: Duration = Duration::from_secs(3);
+/// Git's empty tree, to compare root commits to
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ActiveView {
     Branches,
     Graph,
     Commit,
     Files,
     Diff,
+    Reflog,
     Models,
     Search,
+    Filter,
+    Dialog,
     Help(u16),
 }
 
@@ -37,19 +66,35 @@ pub enum DiffType {
     Renamed,
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DiffMode {
     Diff,
+    Split,
     Old,
     New,
 }
 
+impl DiffMode {
+    /// Whether the mode shows a patch (unified or side-by-side) rather than a file version
+    pub fn is_patch(&self) -> bool {
+        matches!(self, DiffMode::Diff | DiffMode::Split)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct DiffOptions {
     pub context_lines: u32,
     pub diff_mode: DiffMode,
     pub line_numbers: bool,
     pub syntax_highlight: bool,
     pub wrap_lines: bool,
+    pub word_diff: bool,
+    /// Show the changed files grouped by directory
+    pub file_tree: bool,
+    #[serde(skip)]
+    pub blame: bool,
 }
 
 impl Default for DiffOptions {
@@ -60,6 +105,9 @@ impl Default for DiffOptions {
             line_numbers: true,
             syntax_highlight: true,
             wrap_lines: false,
+            word_diff: true,
+            file_tree: false,
+            blame: false,
         }
     }
 }
@@ -73,6 +121,8 @@ impl FromStr for DiffType {
             "D" => DiffType::Deleted,
             "M" => DiffType::Modified,
             "R" => DiffType::Renamed,
+            // Untracked files in the working tree
+            "?" => DiffType::Added,
             other => return Err(format!("Unknown diff type {}", other)),
         };
         Ok(tp)
@@ -91,17 +141,105 @@ impl std::fmt::Display for DiffType {
     }
 }
 
-impl DiffType {
-    pub fn to_color(&self) -> Color {
-        match self {
-            DiffType::Added => Color::LightGreen,
-            DiffType::Deleted => Color::LightRed,
-            DiffType::Modified => Color::LightYellow,
-            DiffType::Renamed => Color::LightBlue,
+/// Border between panels that can be dragged with the mouse
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SplitBorder {
+    /// Between the graph (or diff) panel and the commit and files panels
+    Main,
+    /// Between the commit and files panels
+    Sub,
+}
+
+/// Screen areas of the panels, updated when drawing, and the split ratios between them
+pub struct PanelLayout {
+    pub panels: Vec<(ActiveView, Rect)>,
+    /// Area and direction of the main split
+    pub main: Option<(Rect, Direction)>,
+    /// Area and direction of the split between commit and files panels
+    pub sub: Option<(Rect, Direction)>,
+    /// Percentage of the main split taken by the graph (or diff) panel
+    pub main_percent: u16,
+    /// Percentage of the sub split taken by the commit panel
+    pub sub_percent: u16,
+    pub dragging: Option<SplitBorder>,
+}
+
+impl Default for PanelLayout {
+    fn default() -> Self {
+        Self {
+            panels: vec![],
+            main: None,
+            sub: None,
+            main_percent: 50,
+            sub_percent: 50,
+            dragging: None,
+        }
+    }
+}
+
+impl PanelLayout {
+    /// The panel at a screen position, with the row inside the panel's borders
+    pub fn panel_at(&self, column: u16, row: u16) -> Option<(ActiveView, Option<usize>)> {
+        self.panels
+            .iter()
+            .rev()
+            .find(|(_, area)| contains(area, column, row))
+            .map(|(view, area)| {
+                let inner = row
+                    .checked_sub(area.y + 1)
+                    .filter(|r| *r + 2 < area.height)
+                    .map(|r| r as usize);
+                (*view, inner)
+            })
+    }
+
+    /// The split border at a screen position
+    fn border_at(&self, column: u16, row: u16) -> Option<SplitBorder> {
+        let on_border = |split: &Option<(Rect, Direction)>, percent: u16| {
+            split.as_ref().is_some_and(|(area, direction)| {
+                contains(area, column, row) && {
+                    let (pos, start, size) = match direction {
+                        Direction::Horizontal => (column, area.x, area.width),
+                        Direction::Vertical => (row, area.y, area.height),
+                    };
+                    let split = start + (size as u32 * percent as u32 / 100) as u16;
+                    pos + 1 == split || pos == split
+                }
+            })
+        };
+        if on_border(&self.main, self.main_percent) {
+            Some(SplitBorder::Main)
+        } else if on_border(&self.sub, self.sub_percent) {
+            Some(SplitBorder::Sub)
+        } else {
+            None
+        }
+    }
+
+    /// Moves the dragged border to a screen position
+    fn drag_to(&mut self, column: u16, row: u16) {
+        let (split, percent) = match self.dragging {
+            Some(SplitBorder::Main) => (&self.main, &mut self.main_percent),
+            Some(SplitBorder::Sub) => (&self.sub, &mut self.sub_percent),
+            None => return,
+        };
+        if let Some((area, direction)) = split {
+            let (pos, start, size) = match direction {
+                Direction::Horizontal => (column, area.x, area.width),
+                Direction::Vertical => (row, area.y, area.height),
+            };
+            if size > 0 {
+                let new_percent = (pos.saturating_sub(start) as u32 * 100 / size as u32) as u16;
+                *percent = new_percent.clamp(MIN_SPLIT_PERCENT, 100 - MIN_SPLIT_PERCENT);
+            }
         }
     }
 }
 
+fn contains(area: &Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.right() && row >= area.y && row < area.bottom()
+}
+
 pub type CurrentBranches = Vec<(Option<String>, Option<Oid>)>;
 pub type DiffLines = Vec<(String, Option<u32>, Option<u32>)>;
 
@@ -111,6 +249,8 @@ pub struct App {
     pub commit_state: CommitViewState,
     pub diff_state: DiffViewState,
     pub models_state: Option<ModelListState>,
+    /// The reflog shown in place of the graph, if open
+    pub reflog_state: Option<ReflogState>,
     pub title: String,
     pub repo_name: String,
     pub active_view: ActiveView,
@@ -122,8 +262,28 @@ pub struct App {
     pub color: bool,
     pub models_path: PathBuf,
     pub error_message: Option<String>,
+    /// Action that stopped due to conflicts, with the conflicted paths
+    pub conflicts: Option<(String, Vec<String>)>,
+    /// Message about a successful action, with the time it was set
+    pub status_message: Option<(String, Instant)>,
     pub diff_options: DiffOptions,
     pub search_term: Option<String>,
+    pub filter: CommitFilter,
+    pub filter_input: String,
+    /// Files touched by commits matching a path filter, under their name in the commit
+    pub path_history: PathHistory,
+    /// Name and commit to compare the selected commit to instead of its parent,
+    /// if there is no secondary selection
+    pub compare_target: Option<(String, Oid)>,
+    pub action_dialog: Option<ActionDialog>,
+    pub layout: PanelLayout,
+    pub loader: Option<GraphLoader>,
+    /// Settings and commit limit of the last load, to reload when the filter's range changes
+    pub load_settings: Option<(Settings, Option<usize>)>,
+    /// Number of loaded commits, before restricting the graph to the filter's range
+    pub total_commits: usize,
+    /// Watcher for changes of the repository, or `None` to poll for changes
+    pub watcher: Option<RepoWatcher>,
 }
 
 impl App {
@@ -133,25 +293,41 @@ impl App {
         repo_name: String,
         models_path: PathBuf,
     ) -> App {
+        let horizontal_split = settings.horizontal_split;
+        let show_branches = settings.show_branches;
+        let diff_options = settings.diff_options.clone();
         App {
             settings,
             graph_state: GraphViewState::default(),
             commit_state: CommitViewState::default(),
             diff_state: DiffViewState::default(),
             models_state: None,
+            reflog_state: None,
             title,
             repo_name,
             active_view: ActiveView::Graph,
             prev_active_view: None,
             curr_branches: vec![],
             is_fullscreen: false,
-            horizontal_split: true,
-            show_branches: false,
+            horizontal_split,
+            show_branches,
             color: true,
             models_path,
             error_message: None,
-            diff_options: DiffOptions::default(),
+            conflicts: None,
+            status_message: None,
+            diff_options,
             search_term: None,
+            filter: CommitFilter::default(),
+            filter_input: String::new(),
+            path_history: PathHistory::default(),
+            compare_target: None,
+            action_dialog: None,
+            layout: PanelLayout::default(),
+            loader: None,
+            load_settings: None,
+            total_commits: 0,
+            watcher: None,
         }
     }
 
@@ -163,24 +339,65 @@ impl App {
         indices: Vec<usize>,
         select_head: bool,
     ) -> Result<App, String> {
-        let branches = get_branches(&graph);
+        self.set_graph(graph, graph_lines, text_lines, indices, select_head)?;
+        Ok(self)
+    }
+
+    pub fn set_graph(
+        &mut self,
+        mut graph: GitGraph,
+        graph_lines: Vec<String>,
+        text_lines: Vec<String>,
+        indices: Vec<usize>,
+        select_head: bool,
+    ) -> Result<(), String> {
+        let stashes = git::get_stashes(&mut graph.repository)?;
+        let branches = get_branches(&graph, stashes);
+        let pseudo_commits = get_pseudo_commits(&graph.repository)?;
+        let (mut all_graph_lines, mut all_text_lines) =
+            pseudo_commit_lines(&graph, &pseudo_commits, self.hash_color());
+        all_graph_lines.extend(graph_lines);
+        all_text_lines.extend(text_lines);
 
         self.graph_state.graph = Some(graph);
-        self.graph_state.graph_lines = graph_lines;
-        self.graph_state.text_lines = text_lines;
-        self.graph_state.indices = indices;
+        self.graph_state.graph_lines = all_graph_lines;
+        self.graph_state.text_lines = all_text_lines;
+        self.graph_state.indices = (0..pseudo_commits.len())
+            .chain(indices.into_iter().map(|idx| idx + pseudo_commits.len()))
+            .collect();
+        self.graph_state.pseudo_commits = pseudo_commits;
+        self.update_matches()?;
         self.graph_state.branches = Some(StatefulList::with_items(branches));
 
         if select_head {
             if let Some(graph) = &self.graph_state.graph {
-                if let Some(index) = graph.indices.get(&graph.head.oid) {
-                    self.graph_state.selected = Some(*index);
+                // With a revision range, the end of the range is compared to its start
+                let endpoints = self
+                    .filter
+                    .range
+                    .as_ref()
+                    .and_then(|range| range.endpoints(&graph.repository).ok());
+                let oid = endpoints.map(|(_, to)| to).unwrap_or(graph.head.oid);
+                // If it does not touch the filter's paths, the newest shown commit is selected
+                let index = graph
+                    .indices
+                    .get(&oid)
+                    .or_else(|| graph.indices.get(&graph.commits.first()?.oid));
+                if let Some(index) = index {
+                    if let (Some(range), Some((from, _))) = (&self.filter.range, endpoints) {
+                        self.compare_target = Some((range.compare_name(), from));
+                    }
+                    self.graph_state.selected = Some(self.graph_state.row_index(*index));
+                    self.select_matching();
                     self.selection_changed()?;
+                    if endpoints.is_some() {
+                        self.select_first_file()?;
+                    }
                 }
             }
         }
 
-        Ok(self)
+        Ok(())
     }
 
     pub fn with_branches(mut self, branches: Vec<(Option<String>, Option<Oid>)>) -> App {
@@ -188,46 +405,224 @@ impl App {
         self
     }
 
+    /// Sets the commit filter. Call before `load`, as the graph is restricted to the filter's range.
+    pub fn with_filter(mut self, filter: CommitFilter) -> App {
+        self.filter_input = filter.to_string();
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the color mode. Call before `with_graph`, as graph rows depend on it.
     pub fn with_color(mut self, color: bool) -> App {
         self.color = color;
         self
     }
 
+    /// The color index of commit hashes in text formatted with escape codes, if colored
+    fn hash_color(&self) -> Option<u8> {
+        if self.color {
+            Some(self.settings.theme.hash_index())
+        } else {
+            None
+        }
+    }
+
     pub fn clear_graph(mut self) -> App {
         self.graph_state.graph = None;
         self.graph_state.graph_lines = vec![];
         self.graph_state.text_lines = vec![];
         self.graph_state.indices = vec![];
+        self.graph_state.pseudo_commits = vec![];
+        self.graph_state.matches = None;
         self
     }
 
-    pub fn reload(
-        mut self,
+    /// Starts loading the graph of the repository at `path` in the background.
+    /// With `incremental`, the first commits are shown before all commits are loaded.
+    pub fn load(
+        &mut self,
+        path: PathBuf,
         settings: &Settings,
         max_commits: Option<usize>,
-    ) -> Result<App, String> {
-        let selected = self.graph_state.selected;
-        let mut temp = None;
-        std::mem::swap(&mut temp, &mut self.graph_state.graph);
-        if let Some(graph) = temp {
-            let sel_oid = selected
-                .and_then(|idx| graph.commits.get(idx))
-                .map(|info| info.oid);
-            let repo = graph.take_repository();
-            let graph = GitGraph::new(repo, settings, max_commits)?;
-            let (graph_lines, text_lines, indices) = print_unicode(&graph, settings)?;
-
-            let sel_idx = sel_oid.and_then(|oid| graph.indices.get(&oid)).cloned();
-            let old_idx = self.graph_state.selected;
-            self.graph_state.selected = sel_idx;
-            if sel_idx.is_some() != old_idx.is_some() {
-                self.selection_changed()?;
-            }
-
-            self.with_graph(graph, graph_lines, text_lines, indices, false)
-        } else {
-            Ok(self)
+        incremental: bool,
+    ) {
+        self.loader = Some(GraphLoader::start(
+            path,
+            settings,
+            max_commits,
+            incremental,
+            &self.filter,
+        ));
+        self.load_settings = Some((copy_settings(settings), max_commits));
+    }
+
+    /// Starts reloading the graph in the background. The current graph is kept until done.
+    pub fn reload(&mut self, settings: &Settings, max_commits: Option<usize>) {
+        if let Some(graph) = &self.graph_state.graph {
+            let path = graph.repository.path().to_path_buf();
+            self.load(path, settings, max_commits, false);
+        }
+    }
+
+    /// Reloads the graph with the settings of the last load, to restrict it to the filter's range and paths
+    fn reload_restricted(&mut self) {
+        if let Some((settings, max_commits)) = self.load_settings.take() {
+            self.reload(&settings, max_commits);
+        }
+    }
+
+    /// Updates the pseudo-commits for uncommitted changes, without reloading the graph
+    pub fn reload_pseudo_commits(&mut self) -> Result<(), String> {
+        let graph = match &self.graph_state.graph {
+            Some(graph) => graph,
+            None => return Ok(()),
+        };
+        let pseudo_commits = get_pseudo_commits(&graph.repository)?;
+        let (pseudo_graph_lines, pseudo_text_lines) =
+            pseudo_commit_lines(graph, &pseudo_commits, self.hash_color());
+        let head_oid = graph.head.oid;
+
+        let state = &mut self.graph_state;
+        let old_len = state.pseudo_commits.len();
+        let new_len = pseudo_commits.len();
+        let sel_pseudo = state.selected_pseudo();
+        let sel_commit = state.selected_commit();
+        let sec_commit = state.secondary_selected_commit();
+
+        // Each pseudo-commit has one line
+        state.graph_lines.splice(..old_len, pseudo_graph_lines);
+        state.text_lines.splice(..old_len, pseudo_text_lines);
+        state.indices = (0..new_len)
+            .chain(
+                state.indices[old_len..]
+                    .iter()
+                    .map(|line| line - old_len + new_len),
+            )
+            .collect();
+        if let Some(matches) = &mut state.matches {
+            matches.splice(..old_len, pseudo_commits.iter().map(|_| false));
+        }
+        state.pseudo_commits = pseudo_commits;
+
+        state.selected = match sel_pseudo {
+            Some(pseudo) => state
+                .pseudo_commits
+                .iter()
+                .position(|p| *p == pseudo)
+                .or_else(|| state.row_of_oid(Some(head_oid))),
+            None => sel_commit.map(|idx| state.row_index(idx)),
+        };
+        state.secondary_selected = sec_commit.map(|idx| state.row_index(idx));
+
+        if sel_pseudo.is_some() {
+            self.selection_changed()?;
+        }
+        Ok(())
+    }
+
+    /// Updates the stashes in the branch list, without reloading the graph
+    pub fn reload_stashes(&mut self) -> Result<(), String> {
+        if let Some(graph) = &mut self.graph_state.graph {
+            let stashes = git::get_stashes(&mut graph.repository)?;
+            let mut branches = StatefulList::with_items(get_branches(graph, stashes));
+            if let Some(old) = &self.graph_state.branches {
+                branches.state = old.state.clone();
+                if let Some(sel) = branches.state.selected() {
+                    if sel >= branches.items.len() {
+                        branches.state.select(branches.items.len().checked_sub(1));
+                    }
+                }
+            }
+            self.graph_state.branches = Some(branches);
+        }
+        Ok(())
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    /// Takes the graph from the background loader, if a new one is available.
+    /// Keeps the selected commits.
+    pub fn poll_graph(&mut self) -> Result<(), String> {
+        let loaded = match self.loader.as_mut().and_then(|loader| loader.poll()) {
+            Some(loaded) => loaded,
+            None => return Ok(()),
+        };
+        let mut loaded = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                self.loader = None;
+                self.set_error(err);
+                return Ok(());
+            }
+        };
+        if loaded.complete {
+            self.loader = None;
+        }
+        if let Some(err) = loaded.range_error.take() {
+            // E.g. a branch of the range was deleted, so the range is dropped
+            self.filter.range = None;
+            self.filter_input = self.filter.to_string();
+            self.set_error(err);
+        }
+        self.total_commits = loaded.total_commits;
+        self.path_history = loaded.path_history;
+
+        let old_graph = self.graph_state.graph.take();
+        let old_commit = |index: Option<usize>| {
+            old_graph
+                .as_ref()
+                .and_then(|graph| index.and_then(|idx| graph.commits.get(idx)))
+                .map(|info| info.oid)
+        };
+        let sel_pseudo = self.graph_state.selected_pseudo();
+        let sel_commit = self.graph_state.selected_commit();
+        let sec_oid = old_commit(self.graph_state.secondary_selected_commit());
+        let old_idx = self.graph_state.selected;
+
+        if old_idx.is_none() {
+            self.set_graph(
+                loaded.graph,
+                loaded.graph_lines,
+                loaded.text_lines,
+                loaded.indices,
+                true,
+            )?;
+            return Ok(());
         }
+        self.set_graph(
+            loaded.graph,
+            loaded.graph_lines,
+            loaded.text_lines,
+            loaded.indices,
+            false,
+        )?;
+
+        let state = &self.graph_state;
+        let sel_idx = if let Some(pseudo) = sel_pseudo {
+            state.pseudo_commits.iter().position(|p| *p == pseudo)
+        } else {
+            // If the selected commit is not shown any more, e.g. as it is outside of a new range,
+            // the next shown commit is selected
+            sel_commit
+                .and_then(|idx| {
+                    old_graph
+                        .as_ref()
+                        .and_then(|graph| graph.commits.get(idx..))
+                })
+                .and_then(|commits| {
+                    commits
+                        .iter()
+                        .find_map(|info| state.row_of_oid(Some(info.oid)))
+                })
+        };
+        let sec_idx = state.row_of_oid(sec_oid);
+
+        self.graph_state.selected = sel_idx;
+        self.graph_state.secondary_selected = sec_idx;
+        // Even if the selected commit was kept, e.g. its branches may have changed
+        self.refresh_selection()
     }
 
     pub fn on_up(&mut self, is_shift: bool, is_ctrl: bool) -> Result<(bool, bool), String> {
@@ -260,15 +655,32 @@ impl App {
             }
             ActiveView::Files => {
                 if let Some(content) = &mut self.commit_state.content {
+                    if self.diff_options.file_tree {
+                        content.sync_tree();
+                        content.tree.bwd(step);
+                        return Ok((false, content.select_tree_file()));
+                    }
                     return Ok((false, content.diffs.bwd(step)));
                 }
             }
             ActiveView::Diff => {
                 if let Some(content) = &mut self.diff_state.content {
-                    content.scroll = (
-                        content.scroll.0.saturating_sub(step as u16),
-                        content.scroll.1,
-                    );
+                    if let Some(blame) = &mut content.blame {
+                        blame.move_cursor(step, false);
+                    } else {
+                        content.scroll = (
+                            content.scroll.0.saturating_sub(step as u16),
+                            content.scroll.1,
+                        );
+                    }
+                }
+            }
+            ActiveView::Reflog => {
+                if let Some(state) = &mut self.reflog_state {
+                    if state.entries.bwd(step) {
+                        self.show_reflog_entry()?;
+                        return Ok((false, true));
+                    }
                 }
             }
             ActiveView::Models => {
@@ -311,15 +723,32 @@ impl App {
             }
             ActiveView::Files => {
                 if let Some(content) = &mut self.commit_state.content {
+                    if self.diff_options.file_tree {
+                        content.sync_tree();
+                        content.tree.fwd(step);
+                        return Ok((false, content.select_tree_file()));
+                    }
                     return Ok((false, content.diffs.fwd(step)));
                 }
             }
             ActiveView::Diff => {
                 if let Some(content) = &mut self.diff_state.content {
-                    content.scroll = (
-                        content.scroll.0.saturating_add(step as u16),
-                        content.scroll.1,
-                    );
+                    if let Some(blame) = &mut content.blame {
+                        blame.move_cursor(step, true);
+                    } else {
+                        content.scroll = (
+                            content.scroll.0.saturating_add(step as u16),
+                            content.scroll.1,
+                        );
+                    }
+                }
+            }
+            ActiveView::Reflog => {
+                if let Some(state) = &mut self.reflog_state {
+                    if state.entries.fwd(step) {
+                        self.show_reflog_entry()?;
+                        return Ok((false, true));
+                    }
                 }
             }
             ActiveView::Models => {
@@ -336,7 +765,7 @@ impl App {
         if let ActiveView::Graph = self.active_view {
             if let Some(graph) = &self.graph_state.graph {
                 if let Some(index) = graph.indices.get(&graph.head.oid) {
-                    self.graph_state.selected = Some(*index);
+                    self.graph_state.selected = Some(self.graph_state.row_index(*index));
                     return Ok(true);
                 } else if !self.graph_state.graph_lines.is_empty() {
                     self.graph_state.selected = Some(0);
@@ -372,8 +801,8 @@ impl App {
                 }
                 ActiveView::Files => {
                     if let Some(content) = &mut self.commit_state.content {
-                        content.diffs.state.scroll_x =
-                            content.diffs.state.scroll_x.saturating_add(step as u16);
+                        let state = content.files_state(self.diff_options.file_tree);
+                        state.scroll_x = state.scroll_x.saturating_add(step as u16);
                     }
                 }
                 ActiveView::Branches => {
@@ -382,16 +811,24 @@ impl App {
                             branches.state.scroll_x.saturating_add(step as u16);
                     }
                 }
+                ActiveView::Reflog => {
+                    if let Some(reflog) = &mut self.reflog_state {
+                        reflog.entries.state.scroll_x =
+                            reflog.entries.state.scroll_x.saturating_add(step as u16);
+                    }
+                }
                 _ => {}
             }
         } else {
             self.active_view = match &self.active_view {
+                ActiveView::Branches if self.reflog_state.is_some() => ActiveView::Reflog,
                 ActiveView::Branches => ActiveView::Graph,
-                ActiveView::Graph => ActiveView::Commit,
+                ActiveView::Graph | ActiveView::Reflog => ActiveView::Commit,
                 ActiveView::Commit => {
                     if let Some(commit) = &mut self.commit_state.content {
                         if commit.diffs.state.selected.is_none() && !commit.diffs.items.is_empty() {
-                            commit.diffs.state.selected = Some(0);
+                            commit.diffs.state.selected =
+                                Some(commit.first_file(self.diff_options.file_tree));
                             reload_file_diff = true;
                         }
                     }
@@ -402,6 +839,8 @@ impl App {
                 ActiveView::Help(_) => self.prev_active_view.take().unwrap_or(ActiveView::Graph),
                 ActiveView::Models => ActiveView::Models,
                 ActiveView::Search => ActiveView::Search,
+                ActiveView::Filter => ActiveView::Filter,
+                ActiveView::Dialog => ActiveView::Dialog,
             }
         }
         Ok(reload_file_diff)
@@ -420,8 +859,8 @@ impl App {
                 }
                 ActiveView::Files => {
                     if let Some(content) = &mut self.commit_state.content {
-                        content.diffs.state.scroll_x =
-                            content.diffs.state.scroll_x.saturating_sub(step as u16);
+                        let state = content.files_state(self.diff_options.file_tree);
+                        state.scroll_x = state.scroll_x.saturating_sub(step as u16);
                     }
                 }
                 ActiveView::Branches => {
@@ -430,39 +869,80 @@ impl App {
                             branches.state.scroll_x.saturating_sub(step as u16);
                     }
                 }
+                ActiveView::Reflog => {
+                    if let Some(reflog) = &mut self.reflog_state {
+                        reflog.entries.state.scroll_x =
+                            reflog.entries.state.scroll_x.saturating_sub(step as u16);
+                    }
+                }
                 _ => {}
             }
         } else {
             self.active_view = match &self.active_view {
                 ActiveView::Branches => ActiveView::Branches,
-                ActiveView::Graph => ActiveView::Branches,
+                ActiveView::Graph | ActiveView::Reflog => ActiveView::Branches,
+                ActiveView::Commit if self.reflog_state.is_some() => ActiveView::Reflog,
                 ActiveView::Commit => ActiveView::Graph,
                 ActiveView::Files => ActiveView::Commit,
                 ActiveView::Diff => ActiveView::Files,
                 ActiveView::Help(_) => self.prev_active_view.take().unwrap_or(ActiveView::Graph),
                 ActiveView::Models => ActiveView::Models,
                 ActiveView::Search => ActiveView::Search,
+                ActiveView::Filter => ActiveView::Filter,
+                ActiveView::Dialog => ActiveView::Dialog,
             }
         }
     }
 
-    pub fn on_enter(&mut self, is_control: bool) -> Result<bool, String> {
+    pub fn on_enter(&mut self, is_control: bool) -> Result<(bool, bool), String> {
         match &self.active_view {
             ActiveView::Help(_) => {
                 self.active_view = self.prev_active_view.take().unwrap_or(ActiveView::Graph)
             }
             ActiveView::Search => {
                 self.active_view = self.prev_active_view.take().unwrap_or(ActiveView::Graph);
-                self.search()?;
+                return self.search();
+            }
+            ActiveView::Filter => {
+                self.active_view = self.prev_active_view.take().unwrap_or(ActiveView::Graph);
+                return Ok((self.apply_filter_input()?, false));
+            }
+            ActiveView::Diff => {
+                let oid = self
+                    .diff_state
+                    .content
+                    .as_ref()
+                    .and_then(|content| content.blame.as_ref())
+                    .and_then(|blame| blame.selected_oid());
+                if let Some(oid) = oid {
+                    return Ok((self.select_commit(oid), false));
+                }
+            }
+            ActiveView::Reflog => {
+                let oid = self
+                    .reflog_state
+                    .as_ref()
+                    .and_then(|state| state.selected_oid());
+                if let Some(oid) = oid {
+                    return Ok((self.select_commit(oid), false));
+                }
             }
             ActiveView::Branches => {
+                if let Some((index, oid)) = self.selected_stash() {
+                    self.show_stash(index, oid)?;
+                    return Ok((false, true));
+                }
                 if let Some(graph) = &self.graph_state.graph {
                     if let Some(state) = &self.graph_state.branches {
                         if let Some(sel) = state.state.selected() {
                             let br = &state.items[sel];
                             if let Some(index) = br.index {
                                 let branch_info = &graph.all_branches[index];
-                                let commit_idx = graph.indices[&branch_info.target];
+                                // The branch may be outside of the filter's range
+                                let commit_idx = match graph.indices.get(&branch_info.target) {
+                                    Some(idx) => self.graph_state.row_index(*idx),
+                                    None => return Ok((false, false)),
+                                };
                                 if is_control {
                                     if self.graph_state.selected.is_some() {
                                         self.graph_state.secondary_selected = Some(commit_idx);
@@ -470,15 +950,16 @@ impl App {
                                         if self.is_fullscreen {
                                             self.active_view = ActiveView::Graph;
                                         }
-                                        return Ok(true);
+                                        return Ok((true, false));
                                     }
                                 } else {
                                     self.graph_state.selected = Some(commit_idx);
                                     self.graph_state.secondary_changed = false;
+                                    self.reflog_state = None;
                                     if self.is_fullscreen {
                                         self.active_view = ActiveView::Graph;
                                     }
-                                    return Ok(true);
+                                    return Ok((true, false));
                                 }
                             }
                         }
@@ -487,7 +968,7 @@ impl App {
             }
             _ => {}
         }
-        Ok(false)
+        Ok((false, false))
     }
 
     pub fn on_backspace(&mut self) -> Result<bool, String> {
@@ -503,12 +984,23 @@ impl App {
                     };
                 }
             }
+            ActiveView::Filter => {
+                self.filter_input.pop();
+            }
+            ActiveView::Dialog => {
+                if let Some(dialog) = &mut self.action_dialog {
+                    dialog.on_backspace();
+                }
+            }
             _ => {
                 if self.graph_state.secondary_selected.is_some() {
                     self.graph_state.secondary_selected = None;
                     self.graph_state.secondary_changed = false;
                     return Ok(true);
                 }
+                if self.compare_target.take().is_some() {
+                    return Ok(true);
+                }
             }
         }
         Ok(false)
@@ -543,11 +1035,22 @@ impl App {
                 self.active_view = self.prev_active_view.take().unwrap_or(ActiveView::Graph);
                 self.exit_search(true);
             }
+            ActiveView::Filter => {
+                self.active_view = self.prev_active_view.take().unwrap_or(ActiveView::Graph);
+                self.filter_input = self.filter.to_string();
+            }
+            ActiveView::Dialog => {
+                self.active_view = self.prev_active_view.take().unwrap_or(ActiveView::Graph);
+                self.action_dialog = None;
+            }
             _ => {
                 self.active_view = ActiveView::Graph;
                 self.is_fullscreen = false;
+                if self.reflog_state.take().is_some() {
+                    self.selection_changed()?;
+                }
                 if let Some(content) = &mut self.commit_state.content {
-                    content.diffs.state.scroll_x = 0;
+                    content.files_state(self.diff_options.file_tree).scroll_x = 0;
                 }
                 self.diff_options.diff_mode = DiffMode::Diff;
                 return Ok(true);
@@ -558,19 +1061,25 @@ impl App {
     }
 
     pub fn character_entered(&mut self, c: char) {
-        if let ActiveView::Search = self.active_view {
-            if let Some(term) = &self.search_term {
-                self.search_term = Some(format!("{}{}", term, c))
-            } else {
-                self.search_term = Some(format!("{}", c))
+        match self.active_view {
+            ActiveView::Search => {
+                if let Some(term) = &self.search_term {
+                    self.search_term = Some(format!("{}{}", term, c))
+                } else {
+                    self.search_term = Some(format!("{}", c))
+                }
+            }
+            ActiveView::Filter => self.filter_input.push(c),
+            ActiveView::Dialog => {
+                if let Some(dialog) = &mut self.action_dialog {
+                    dialog.character_entered(c);
+                }
             }
+            _ => {}
         }
     }
 
     pub fn open_search(&mut self) {
-        // TODO: remove once searching in diffs works
-        self.active_view = ActiveView::Graph;
-
         if let ActiveView::Search = self.active_view {
         } else {
             let mut temp = ActiveView::Search;
@@ -580,110 +1089,1100 @@ impl App {
     }
     pub fn exit_search(&mut self, _abort: bool) {}
 
-    pub fn search(&mut self) -> Result<bool, String> {
-        // TODO: remove once searching in diffs works
-        self.active_view = ActiveView::Graph;
+    fn open_action(&mut self, action: RepoAction) {
+        self.action_dialog = Some(ActionDialog::new(action));
+        if let ActiveView::Dialog = self.active_view {
+        } else {
+            let mut temp = ActiveView::Dialog;
+            std::mem::swap(&mut temp, &mut self.active_view);
+            self.prev_active_view = Some(temp);
+        }
+    }
 
-        let update = match &self.active_view {
-            ActiveView::Branches | ActiveView::Graph | ActiveView::Commit => self.search_graph()?,
-            ActiveView::Files | ActiveView::Diff => self.search_diff(),
-            _ => false,
-        };
-        Ok(update)
+    fn selected_commit_oid(&self) -> Option<Oid> {
+        let graph = self.graph_state.graph.as_ref()?;
+        self.graph_state
+            .selected_commit()
+            .map(|idx| graph.commits[idx].oid)
     }
-    fn search_graph(&mut self) -> Result<bool, String> {
-        if let Some(search) = &self.search_term {
-            let term = search.to_lowercase();
 
-            let search_start = if let Some(sel_idx) = &self.graph_state.selected {
-                sel_idx + 1
-            } else {
-                0
-            };
-            for idx in search_start..self.graph_state.indices.len() {
-                if self.commit_contains(idx, &term) {
-                    self.graph_state.selected = Some(idx);
-                    return Ok(true);
+    fn selected_local_branch(&self) -> Option<String> {
+        let branches = self.graph_state.branches.as_ref()?;
+        let item = &branches.items[branches.state.selected()?];
+        if item.branch_type == BranchItemType::LocalBranch {
+            Some(item.name.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Index and commit of the stash selected in the branches panel
+    fn selected_stash(&self) -> Option<(usize, Oid)> {
+        let branches = self.graph_state.branches.as_ref()?;
+        match branches.items[branches.state.selected()?].branch_type {
+            BranchItemType::Stash { index, oid } => Some((index, oid)),
+            _ => None,
+        }
+    }
+
+    /// Shows a stash in the commit, files and diff panels, instead of the selected commit
+    fn show_stash(&mut self, index: usize, oid: Oid) -> Result<(), String> {
+        let (text, compare_oid) = match &self.graph_state.graph {
+            Some(graph) => {
+                let commit = graph
+                    .repository
+                    .find_commit(oid)
+                    .map_err(|err| err.message().to_string())?;
+                let hash_color = self.hash_color();
+                let name = format!(" (stash@{{{}}})", index);
+                let mut text = crate::util::format::format(&commit, name, hash_color);
+                // The parents of a stash are its base commit and the index, rather than a merge
+                if let Some(line) = text.iter_mut().find(|line| line.starts_with("  Merge:")) {
+                    *line = if commit.parent_count() > 2 {
+                        "  Includes untracked files".to_string()
+                    } else {
+                        String::new()
+                    };
                 }
+                (text, commit.parent_id(0).unwrap_or_else(|_| Oid::zero()))
             }
-            for idx in 0..search_start {
-                if self.commit_contains(idx, &term) {
-                    self.graph_state.selected = Some(idx);
-                    return Ok(true);
-                }
+            None => return Ok(()),
+        };
+
+        let mut info = CommitViewInfo::new(text, StatefulList::default(), oid, compare_oid);
+        info.pseudo = Some(PseudoCommit::Stash(index));
+        self.commit_state.content = Some(info);
+        self.reload_diff_files()?;
+        Ok(())
+    }
+
+    /// Shows the reflog of HEAD (graph panel) or of the selected local branch (branches panel)
+    /// in place of the graph, or closes it if open
+    pub fn toggle_reflog(&mut self) -> Result<(), String> {
+        if self.reflog_state.is_some() {
+            self.reflog_state = None;
+            if self.active_view == ActiveView::Reflog {
+                self.active_view = ActiveView::Graph;
             }
+            return self.selection_changed();
         }
-        Ok(false)
+        let name = match self.active_view {
+            ActiveView::Graph => "HEAD".to_string(),
+            ActiveView::Branches => match self.selected_local_branch() {
+                Some(name) => format!("refs/heads/{}", name),
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        let graph = match &self.graph_state.graph {
+            Some(graph) => graph,
+            None => return Ok(()),
+        };
+        let short_name = name.strip_prefix("refs/heads/").unwrap_or(&name);
+        let entries = git::get_reflog(&graph.repository, &name)?
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (old, new, message, time))| ReflogItem {
+                selector: format!("{}@{{{}}}", short_name, idx),
+                old,
+                new,
+                message,
+                time: format_date(time, "%Y-%m-%d %H:%M"),
+            })
+            .collect::<Vec<_>>();
+        if entries.is_empty() {
+            return Err(format!("No reflog entries for {}", short_name));
+        }
+
+        let mut state = ReflogState::new(name, entries);
+        state.entries.state.select(Some(0));
+        self.reflog_state = Some(state);
+        self.active_view = ActiveView::Reflog;
+        self.show_reflog_entry()?;
+        Ok(())
     }
 
-    fn commit_contains(&self, commit_idx: usize, term: &str) -> bool {
-        let num_lines = self.graph_state.text_lines.len();
-        let line_start = self.graph_state.indices[commit_idx];
-        let line_end = self
-            .graph_state
-            .indices
-            .get(commit_idx + 1)
-            .unwrap_or(&num_lines);
-        for line_idx in line_start..*line_end {
-            if self.graph_state.text_lines[line_idx]
-                .to_lowercase()
-                .contains(term)
-            {
-                return true;
+    /// Shows the commit of the selected reflog entry in the commit, files and diff panels.
+    /// The commit is loaded from the repository, as it may not be contained in the graph.
+    fn show_reflog_entry(&mut self) -> Result<(), String> {
+        let (oid, selector) = match self.reflog_state.as_ref().and_then(|state| {
+            let idx = state.entries.state.selected()?;
+            state.entries.items.get(idx)
+        }) {
+            Some(item) => (item.new, item.selector.clone()),
+            None => return Ok(()),
+        };
+        let (text, compare_oid) = match &self.graph_state.graph {
+            Some(graph) => {
+                let commit = graph
+                    .repository
+                    .find_commit(oid)
+                    .map_err(|err| err.message().to_string())?;
+                let hash_color = self.hash_color();
+                let mut text = crate::util::format::format(&commit, String::new(), hash_color);
+                text[1] = format!(" ({})", selector);
+                text[2] = if graph.indices.contains_key(&oid) {
+                    String::new()
+                } else {
+                    "  Not contained in the graph".to_string()
+                };
+                (text, commit.parent_id(0).unwrap_or_else(|_| Oid::zero()))
+            }
+            None => return Ok(()),
+        };
+
+        let info = CommitViewInfo::new(text, StatefulList::default(), oid, compare_oid);
+        self.commit_state.content = Some(info);
+        self.reload_diff_files()?;
+        Ok(())
+    }
+
+    /// Asks to apply the stash selected in the branches panel, and to remove it with `pop`
+    pub fn apply_stash(&mut self, pop: bool) {
+        if self.active_view == ActiveView::Branches {
+            if let Some((index, _)) = self.selected_stash() {
+                self.open_action(if pop {
+                    RepoAction::PopStash(index)
+                } else {
+                    RepoAction::ApplyStash(index)
+                });
             }
         }
-        false
     }
 
-    fn search_diff(&mut self) -> bool {
-        // TODO implement search in diff panel
-        false
+    /// Asks to checkout the selected commit (graph panel) or local branch (branches panel)
+    pub fn checkout(&mut self) {
+        let action = match self.active_view {
+            ActiveView::Graph => self.selected_commit_oid().map(RepoAction::CheckoutCommit),
+            ActiveView::Branches => self.selected_local_branch().map(RepoAction::CheckoutBranch),
+            _ => None,
+        };
+        if let Some(action) = action {
+            self.open_action(action);
+        }
     }
 
-    pub fn set_diff_mode(&mut self, mode: DiffMode) -> Result<bool, String> {
-        if mode != self.diff_options.diff_mode
-            && (self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files)
-        {
-            self.diff_options.diff_mode = mode;
-            return Ok(true);
+    /// Asks to delete the local branch or to drop the stash selected in the branches panel
+    pub fn delete_branch(&mut self) {
+        if self.active_view == ActiveView::Branches {
+            if let Some(name) = self.selected_local_branch() {
+                self.open_action(RepoAction::DeleteBranch(name));
+            } else if let Some((index, _)) = self.selected_stash() {
+                self.open_action(RepoAction::DropStash(index));
+            }
         }
-        Ok(false)
     }
 
-    pub fn toggle_line_numbers(&mut self) -> Result<bool, String> {
-        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
-            self.diff_options.line_numbers = !self.diff_options.line_numbers;
-            return Ok(true);
+    /// Asks for the name of a branch to create at the selected commit
+    pub fn create_branch(&mut self) {
+        let oid = match self.active_view {
+            ActiveView::Graph => self.selected_commit_oid(),
+            ActiveView::Reflog => self.reflog_state.as_ref().and_then(|s| s.selected_oid()),
+            _ => None,
+        };
+        if let Some(oid) = oid {
+            self.open_action(RepoAction::CreateBranch(oid));
         }
-        Ok(false)
     }
 
-    pub fn toggle_line_wrap(&mut self) -> Result<bool, String> {
-        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
-            self.diff_options.wrap_lines = !self.diff_options.wrap_lines;
-            return Ok(true);
+    /// Asks to cherry-pick or to revert the selected commit, and for the mainline parent of merges
+    pub fn cherry_pick(&mut self, revert: bool) {
+        if self.active_view != ActiveView::Graph {
+            return;
         }
-        Ok(false)
+        let (oid, parents) = match (&self.graph_state.graph, self.selected_commit_oid()) {
+            (Some(graph), Some(oid)) => (
+                oid,
+                graph
+                    .repository
+                    .find_commit(oid)
+                    .map(|commit| commit.parent_count())
+                    .map_err(|err| err.message().to_string()),
+            ),
+            _ => return,
+        };
+        let parents = match parents {
+            Ok(parents) => parents,
+            Err(err) => return self.set_error(err),
+        };
+        self.open_action(match (revert, parents > 1) {
+            (false, false) => RepoAction::CherryPick(oid),
+            (false, true) => RepoAction::CherryPickMerge(oid, parents),
+            (true, false) => RepoAction::Revert(oid),
+            (true, true) => RepoAction::RevertMerge(oid, parents),
+        });
     }
 
-    pub fn toggle_syntax_highlight(&mut self) -> Result<bool, String> {
-        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
-            self.diff_options.syntax_highlight = !self.diff_options.syntax_highlight;
-            return Ok(true);
+    /// Asks for a branch, tag or commit to compare the selected commit to
+    pub fn open_compare(&mut self) {
+        if matches!(
+            self.active_view,
+            ActiveView::Graph | ActiveView::Commit | ActiveView::Files | ActiveView::Diff
+        ) {
+            self.open_action(RepoAction::CompareTo);
         }
-        Ok(false)
     }
 
-    pub fn toggle_layout(&mut self) {
-        self.horizontal_split = !self.horizontal_split;
+    /// Asks for the name of a tag to create at the selected commit
+    pub fn create_tag(&mut self, annotated: bool) {
+        if self.active_view == ActiveView::Graph {
+            if let Some(oid) = self.selected_commit_oid() {
+                self.open_action(if annotated {
+                    RepoAction::CreateAnnotatedTag(oid)
+                } else {
+                    RepoAction::CreateTag(oid)
+                });
+            }
+        }
     }
 
-    pub fn toggle_branches(&mut self) {
-        self.show_branches = !self.show_branches;
+    /// Asks for the file to export the graph to
+    pub fn open_export(&mut self) {
+        if self.graph_state.graph.is_some() {
+            self.open_action(RepoAction::ExportGraph);
+        }
     }
 
-    pub fn show_help(&mut self) {
-        if let ActiveView::Help(_) = self.active_view {
+    /// Exports the graph, without uncommitted changes, to an SVG or HTML file.
+    /// The format is determined by the file extension.
+    pub fn export_graph(&self, path: &Path) -> Result<(), String> {
+        let format = ExportFormat::from_path(path)?;
+        let state = &self.graph_state;
+        let start = state
+            .indices
+            .get(state.pseudo_commits.len())
+            .cloned()
+            .unwrap_or(state.graph_lines.len());
+        let content = export_graph(
+            &state.graph_lines[start..],
+            &state.text_lines[start..],
+            format,
+            &self.title,
+        );
+        std::fs::write(path, content)
+            .map_err(|err| format!("Unable to write file {}\n{}", path.display(), err))
+    }
+
+    /// Performs the action of the open dialog, or continues with the next dialog.
+    /// Errors are shown to the user. Returns whether the repository was changed.
+    pub fn confirm_action(&mut self) -> bool {
+        let dialog = match self.action_dialog.take() {
+            Some(dialog) => dialog,
+            None => return false,
+        };
+        self.active_view = self.prev_active_view.take().unwrap_or(ActiveView::Graph);
+
+        if dialog.action == RepoAction::ExportGraph {
+            let input = dialog.input.unwrap_or_default();
+            let path = Path::new(input.trim());
+            match self.export_graph(path) {
+                Ok(()) => self.set_status(format!("Exported graph to {}", path.display())),
+                Err(err) => self.set_error(err),
+            }
+            return false;
+        }
+
+        if dialog.action == RepoAction::CompareTo {
+            let input = dialog.input.unwrap_or_default();
+            let input = input.trim();
+            if input.is_empty() {
+                self.compare_target = None;
+            } else if let Some(graph) = &self.graph_state.graph {
+                match git::resolve_commit(&graph.repository, input) {
+                    Ok(oid) => self.compare_target = Some((input.to_string(), oid)),
+                    Err(err) => self.set_error(err),
+                }
+            }
+            return false;
+        }
+
+        let graph = match &mut self.graph_state.graph {
+            Some(graph) => graph,
+            None => return false,
+        };
+        let repo = &mut graph.repository;
+        let input = dialog.input.as_deref().map(|input| input.trim());
+        if input == Some("") {
+            return false;
+        }
+        let input = input.unwrap_or("");
+        let mainline = |parents: usize| match input.parse::<u32>() {
+            Ok(parent) if parent >= 1 && parent as usize <= parents => Ok(Some(parent)),
+            _ => Err(format!(
+                "Invalid mainline parent '{}'. Expected a number from 1 to {}",
+                input, parents
+            )),
+        };
+        let mut conflicts = vec![];
+        let title = match dialog.action {
+            RepoAction::Revert(_) | RepoAction::RevertMerge(_, _) => "Revert",
+            _ => "Cherry-pick",
+        };
+
+        let result = match dialog.action {
+            RepoAction::CheckoutCommit(oid) => git::checkout_commit(repo, oid),
+            RepoAction::CheckoutBranch(name) => git::checkout_branch(repo, &name),
+            RepoAction::DeleteBranch(name) => git::delete_branch(repo, &name),
+            RepoAction::CreateBranch(oid) => git::create_branch(repo, oid, input),
+            RepoAction::CreateTag(oid) => git::create_tag(repo, oid, input, None),
+            RepoAction::CreateAnnotatedTag(oid) => {
+                self.open_action(RepoAction::TagMessage(oid, input.to_string()));
+                return false;
+            }
+            RepoAction::TagMessage(oid, name) => git::create_tag(repo, oid, &name, Some(input)),
+            RepoAction::ApplyStash(index) => git::apply_stash(repo, index, false),
+            RepoAction::PopStash(index) => git::apply_stash(repo, index, true),
+            RepoAction::DropStash(index) => git::drop_stash(repo, index),
+            RepoAction::CherryPick(oid) => git::cherry_pick(repo, oid, None).map(|c| conflicts = c),
+            RepoAction::CherryPickMerge(oid, parents) => mainline(parents)
+                .and_then(|mainline| git::cherry_pick(repo, oid, mainline))
+                .map(|c| conflicts = c),
+            RepoAction::Revert(oid) => git::revert(repo, oid, None).map(|c| conflicts = c),
+            RepoAction::RevertMerge(oid, parents) => mainline(parents)
+                .and_then(|mainline| git::revert(repo, oid, mainline))
+                .map(|c| conflicts = c),
+            RepoAction::ExportGraph | RepoAction::CompareTo => Ok(()),
+        };
+        match result {
+            Ok(()) => {
+                if !conflicts.is_empty() {
+                    self.conflicts = Some((title.to_string(), conflicts));
+                }
+                true
+            }
+            Err(err) => {
+                self.set_error(err);
+                false
+            }
+        }
+    }
+
+    pub fn open_filter(&mut self) {
+        if let ActiveView::Filter = self.active_view {
+        } else {
+            let mut temp = ActiveView::Filter;
+            std::mem::swap(&mut temp, &mut self.active_view);
+            self.prev_active_view = Some(temp);
+        }
+    }
+
+    /// Parses and applies the filter entered in the filter dialog.
+    /// Returns whether the selection changed.
+    fn apply_filter_input(&mut self) -> Result<bool, String> {
+        match CommitFilter::parse(&self.filter_input) {
+            Ok(filter) => self.set_filter(filter),
+            Err(err) => {
+                self.set_error(err);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Sets the commit filter, and moves the selection to a matching commit if required.
+    /// Returns whether the selection changed.
+    pub fn set_filter(&mut self, filter: CommitFilter) -> Result<bool, String> {
+        let reload = filter.range != self.filter.range || filter.paths != self.filter.paths;
+        self.filter_input = filter.to_string();
+        self.filter = filter;
+        if reload {
+            self.reload_restricted();
+        }
+        self.update_matches()?;
+        Ok(self.select_matching())
+    }
+
+    /// Moves the selection to the next matching row if the selected one does not match the filter.
+    /// Returns whether the selection changed.
+    fn select_matching(&mut self) -> bool {
+        let state = &mut self.graph_state;
+        if let Some(sel) = state.selected {
+            if !state.is_match(sel) {
+                state.selected = (sel..state.indices.len())
+                    .chain((0..sel).rev())
+                    .find(|row| state.is_match(*row));
+                if state
+                    .secondary_selected
+                    .map(|row| !state.is_match(row))
+                    .unwrap_or(false)
+                {
+                    state.secondary_selected = None;
+                }
+                state.secondary_changed = false;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Determines for each row whether it matches the filter
+    fn update_matches(&mut self) -> Result<(), String> {
+        self.graph_state.matches = None;
+        if !self.filter.dims_commits() {
+            return Ok(());
+        }
+        if let Some(graph) = &self.graph_state.graph {
+            // The graph is already restricted to the range and paths
+            let pseudo = self.graph_state.pseudo_commits.iter().map(|_| false);
+            let commits = graph.commits.iter().map(|info| {
+                graph
+                    .repository
+                    .find_commit(info.oid)
+                    .map(|commit| self.filter.matches(&commit))
+                    .unwrap_or(false)
+            });
+            self.graph_state.matches = Some(pseudo.chain(commits).collect());
+        }
+        Ok(())
+    }
+
+    /// Copies the hash of the selected commit (graph), the commit message (commit panel),
+    /// the path of the selected file (files panel) or the current hunk (diff panel)
+    /// to the clipboard. With `short`, copies the short hash of the selected commit.
+    pub fn yank(&mut self, short: bool) {
+        let text = if short {
+            self.selected_commit_oid()
+                .map(|oid| oid.to_string()[..7].to_string())
+        } else {
+            match self.active_view {
+                ActiveView::Graph => self.selected_commit_oid().map(|oid| oid.to_string()),
+                ActiveView::Reflog => self
+                    .reflog_state
+                    .as_ref()
+                    .and_then(|state| state.selected_oid())
+                    .map(|oid| oid.to_string()),
+                ActiveView::Commit => self.commit_message(),
+                ActiveView::Files => self.selected_file().map(|file| file.to_string()),
+                ActiveView::Diff => self.current_hunk(),
+                _ => None,
+            }
+        };
+        let text = match text {
+            Some(text) => text,
+            None => return,
+        };
+        match copy_to_clipboard(&text, &self.settings.clipboard_command) {
+            Ok(()) => {
+                let num_lines = text.lines().count();
+                let message = if num_lines > 1 {
+                    format!("Copied {} lines", num_lines)
+                } else {
+                    format!("Copied '{}'", text.trim_end())
+                };
+                self.set_status(message);
+            }
+            Err(err) => self.set_error(err),
+        }
+    }
+
+    /// Full message of the commit shown in the commit panel
+    fn commit_message(&self) -> Option<String> {
+        let content = self.commit_state.content.as_ref()?;
+        // Uncommitted changes have no commit, in contrast to stashes
+        if content.oid.is_zero() {
+            return None;
+        }
+        let graph = self.graph_state.graph.as_ref()?;
+        let commit = graph.repository.find_commit(content.oid).ok()?;
+        commit.message().map(|msg| msg.trim_end().to_string())
+    }
+
+    /// Index of the diff line shown at the top of the diff panel
+    fn diff_scroll_line(&self) -> Option<usize> {
+        let content = self.diff_state.content.as_ref()?;
+        let diffs = &content.diffs;
+        let num_rows = |idx: usize| diffs[idx].0.lines().count().max(1);
+        let entries: Vec<(usize, usize)> = if self.diff_options.diff_mode == DiffMode::Split {
+            split_lines(diffs)
+                .iter()
+                .filter_map(|row| match row {
+                    SplitLine::Header(idx) => Some((*idx, num_rows(*idx))),
+                    SplitLine::Lines(old, new) => new.or(*old).map(|idx| (idx, 1)),
+                })
+                .collect()
+        } else {
+            (0..diffs.len()).map(|idx| (idx, num_rows(idx))).collect()
+        };
+
+        let mut row = 0;
+        for (idx, rows) in &entries {
+            row += rows;
+            if row > content.scroll.0 as usize {
+                return Some(*idx);
+            }
+        }
+        entries.last().map(|(idx, _)| *idx)
+    }
+
+    /// The hunk at the top of the diff panel, or the first hunk of the file
+    /// if the panel is scrolled to the file header
+    fn current_hunk(&self) -> Option<String> {
+        if !self.diff_options.diff_mode.is_patch() {
+            return None;
+        }
+        let diffs = &self.diff_state.content.as_ref()?.diffs;
+        let top = self.diff_scroll_line()?;
+        let is_hunk = |idx: &usize| diffs[*idx].0.starts_with("@@");
+        let is_header = |idx: &usize| is_hunk(idx) || diffs[*idx].0.starts_with("diff --git");
+
+        let start = (0..=top).rev().find(is_header)?;
+        let start = if is_hunk(&start) {
+            start
+        } else {
+            (start..diffs.len()).find(is_hunk)?
+        };
+        let end = (start + 1..diffs.len())
+            .find(is_header)
+            .unwrap_or(diffs.len());
+        Some(
+            diffs[start..end]
+                .iter()
+                .map(|(text, _, _)| &text[..])
+                .collect(),
+        )
+    }
+
+    /// Path of the file selected in the files panel
+    fn selected_file(&self) -> Option<&str> {
+        let content = self.commit_state.content.as_ref()?;
+        let sel = content.diffs.state.selected()?;
+        Some(&content.diffs.items[sel].file)
+    }
+
+    /// Line of the new file version at the blame cursor or the top of the diff panel
+    fn diff_cursor_line(&self) -> Option<u32> {
+        let content = self.diff_state.content.as_ref()?;
+        match self.diff_options.diff_mode {
+            DiffMode::New => {
+                if let Some(blame) = &content.blame {
+                    return Some(blame.cursor as u32 + 1);
+                }
+                let header_rows = content.diffs.first()?.0.lines().count();
+                Some((content.scroll.0 as usize).saturating_sub(header_rows) as u32 + 1)
+            }
+            DiffMode::Old => None,
+            DiffMode::Diff | DiffMode::Split => {
+                let top = self.diff_scroll_line()?;
+                content.diffs[top..].iter().find_map(|(_, _, new)| *new)
+            }
+        }
+    }
+
+    /// Command to open the selected file in the user's editor,
+    /// at the line under the diff cursor if the diff panel shows the file
+    pub fn editor_command(&self) -> Result<Option<ExternalCommand>, String> {
+        let (graph, file) = match (&self.graph_state.graph, self.selected_file()) {
+            (Some(graph), Some(file)) => (graph, file),
+            _ => return Ok(None),
+        };
+        let workdir = graph
+            .repository
+            .workdir()
+            .ok_or_else(|| "Repository has no working directory".to_string())?;
+
+        let mut command = external::editor();
+        if let Some(line) = self.diff_cursor_line() {
+            command.arg(format!("+{}", line));
+        }
+        command.arg(workdir.join(file)).current_dir(workdir);
+        Ok(Some(ExternalCommand::new(command)))
+    }
+
+    /// Command to compare the versions of the selected file in `git difftool`
+    pub fn difftool_command(&self) -> Result<Option<ExternalCommand>, String> {
+        let (graph, content, file) = match (
+            &self.graph_state.graph,
+            &self.commit_state.content,
+            self.selected_file(),
+        ) {
+            (Some(graph), Some(content), Some(file)) => (graph, content, file),
+            _ => return Ok(None),
+        };
+        let repository = &graph.repository;
+
+        let mut command = Command::new("git");
+        command
+            .current_dir(repository.workdir().unwrap_or_else(|| repository.path()))
+            .args(["difftool", "--no-prompt"]);
+        match content.pseudo {
+            Some(PseudoCommit::Staged) => {
+                command.arg("--cached");
+            }
+            Some(PseudoCommit::Unstaged) => {}
+            Some(PseudoCommit::Stash(_)) | None => {
+                let compare = if content.compare_oid.is_zero() {
+                    EMPTY_TREE.to_string()
+                } else {
+                    content.compare_oid.to_string()
+                };
+                command.arg(compare).arg(content.oid.to_string());
+            }
+        }
+        command.arg("--").arg(file);
+        Ok(Some(ExternalCommand::new(command)))
+    }
+
+    /// Command to show the full patch of the commit in the user's pager
+    pub fn pager_command(&self) -> Result<Option<ExternalCommand>, String> {
+        let (graph, content) = match (&self.graph_state.graph, &self.commit_state.content) {
+            (Some(graph), Some(content)) => (graph, content),
+            _ => return Ok(None),
+        };
+        let repository = &graph.repository;
+
+        let mut patch = String::new();
+        if !content.oid.is_zero() {
+            let commit = repository
+                .find_commit(content.oid)
+                .map_err(|err| err.message().to_string())?;
+            for (idx, line) in crate::util::format::format(&commit, String::new(), None)
+                .iter()
+                .enumerate()
+            {
+                match idx {
+                    0 => writeln!(patch, "commit {}", line).unwrap(),
+                    // Branches and merge parents
+                    1 | 2 if line.is_empty() => {}
+                    _ => writeln!(patch, "{}", line).unwrap(),
+                }
+            }
+        }
+
+        let mut opts = GDiffOptions::new();
+        opts.context_lines(self.diff_options.context_lines);
+        opts.indent_heuristic(true);
+        let diff = create_diff(
+            repository,
+            content.oid,
+            content.compare_oid,
+            content.pseudo,
+            &mut opts,
+        )?;
+        diff.print(DiffFormat::Patch, |d, h, l| {
+            patch.push_str(&print_diff_line(&d, &h, &l));
+            true
+        })
+        .map_err(|err| err.message().to_string())?;
+
+        Ok(Some(ExternalCommand::new(external::pager()).input(patch)))
+    }
+
+    /// Limits the history to commits touching the file selected in the files panel,
+    /// or removes the limit if it is already set to that file.
+    /// Returns whether the selection changed.
+    pub fn toggle_file_history(&mut self) -> Result<bool, String> {
+        if let Some(content) = &self.commit_state.content {
+            if let Some(sel) = content.diffs.state.selected() {
+                let file = content.diffs.items[sel].file.clone();
+                let mut filter = self.filter.clone();
+                filter.paths = if self.path_history.commits.get(&content.oid) == Some(&file) {
+                    vec![]
+                } else {
+                    vec![file]
+                };
+                return self.set_filter(filter);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Selects the next match of the search term in the active panel.
+    /// Shows a status message if there is none.
+    pub fn search(&mut self) -> Result<(bool, bool), String> {
+        let (found, update) = match &self.active_view {
+            ActiveView::Branches | ActiveView::Graph | ActiveView::Commit => {
+                let found = self.search_graph()?;
+                (found, (found, false))
+            }
+            ActiveView::Files => match self.search_files() {
+                Some(changed) => (true, (false, changed)),
+                None => (false, (false, false)),
+            },
+            ActiveView::Diff => (self.search_diff(), (false, false)),
+            _ => (true, (false, false)),
+        };
+        if !found {
+            if let Some(term) = &self.search_term {
+                self.set_status(format!("Not found: {}", term));
+            }
+        }
+        Ok(update)
+    }
+    fn search_graph(&mut self) -> Result<bool, String> {
+        if let Some(search) = &self.search_term {
+            let term = fold_case(search);
+
+            let search_start = if let Some(sel_idx) = &self.graph_state.selected {
+                sel_idx + 1
+            } else {
+                0
+            };
+            for idx in search_start..self.graph_state.indices.len() {
+                if self.commit_contains(idx, &term) {
+                    self.graph_state.selected = Some(idx);
+                    return Ok(true);
+                }
+            }
+            for idx in 0..search_start {
+                if self.commit_contains(idx, &term) {
+                    self.graph_state.selected = Some(idx);
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn commit_contains(&self, commit_idx: usize, term: &str) -> bool {
+        if !self.graph_state.is_match(commit_idx) {
+            return false;
+        }
+        let num_lines = self.graph_state.text_lines.len();
+        let line_start = self.graph_state.indices[commit_idx];
+        let line_end = self
+            .graph_state
+            .indices
+            .get(commit_idx + 1)
+            .unwrap_or(&num_lines);
+        for line_idx in line_start..*line_end {
+            if fold_case(&self.graph_state.text_lines[line_idx]).contains(term) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Selects the next file matching the search term.
+    /// Returns whether the selection changed, or `None` if no file matches.
+    fn search_files(&mut self) -> Option<bool> {
+        if let (Some(search), Some(content)) = (&self.search_term, &mut self.commit_state.content) {
+            let term = fold_case(search);
+            let diffs = &mut content.diffs;
+
+            let search_start = diffs.state.selected().map(|idx| idx + 1).unwrap_or(0);
+            let num_items = diffs.items.len();
+            for idx in (search_start..num_items).chain(0..search_start.min(num_items)) {
+                if fold_case(&diffs.items[idx].file).contains(&term) {
+                    let changed = diffs.state.selected() != Some(idx);
+                    diffs.state.select(Some(idx));
+                    return Some(changed);
+                }
+            }
+        }
+        None
+    }
+
+    fn search_diff(&mut self) -> bool {
+        if let (Some(search), Some(content)) = (&self.search_term, &mut self.diff_state.content) {
+            let term = fold_case(search);
+            let lines: Vec<_> = if self.diff_options.diff_mode == DiffMode::Split {
+                let diffs = &content.diffs;
+                split_lines(diffs)
+                    .iter()
+                    .flat_map(|row| match row {
+                        SplitLine::Header(idx) => {
+                            diffs[*idx].0.lines().map(|l| l.to_string()).collect()
+                        }
+                        SplitLine::Lines(old, new) => {
+                            let old = old.map(|idx| diffs[idx].0.trim_end()).unwrap_or("");
+                            let new = new.map(|idx| diffs[idx].0.trim_end()).unwrap_or("");
+                            vec![format!("{} {}", old, new)]
+                        }
+                    })
+                    .collect()
+            } else {
+                content
+                    .diffs
+                    .iter()
+                    .flat_map(|(text, _, _)| text.lines().map(|l| l.to_string()))
+                    .collect()
+            };
+
+            let search_start = content
+                .search_line
+                .map(|line| line + 1)
+                .or_else(|| {
+                    content
+                        .blame
+                        .as_ref()
+                        .map(|blame| blame.header_lines + blame.cursor)
+                })
+                .unwrap_or(content.scroll.0 as usize)
+                .min(lines.len());
+            for idx in (search_start..lines.len()).chain(0..search_start) {
+                if fold_case(&lines[idx]).contains(&term) {
+                    content.search_line = Some(idx);
+                    match &mut content.blame {
+                        // The blame cursor is kept visible when drawing
+                        Some(blame) => {
+                            blame.cursor = idx
+                                .saturating_sub(blame.header_lines)
+                                .min(blame.lines.len().saturating_sub(1))
+                        }
+                        None => content.scroll_to_search = true,
+                    }
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    pub fn set_diff_mode(&mut self, mode: DiffMode) -> Result<bool, String> {
+        if mode != self.diff_options.diff_mode
+            && (self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files)
+        {
+            self.diff_options.diff_mode = mode;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn toggle_line_numbers(&mut self) -> Result<bool, String> {
+        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
+            self.diff_options.line_numbers = !self.diff_options.line_numbers;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Toggles line wrapping, except in split mode, as wrapping would misalign the columns
+    pub fn toggle_line_wrap(&mut self) -> Result<bool, String> {
+        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
+            if self.diff_options.diff_mode == DiffMode::Split {
+                self.set_status("Line wrapping is not available in split mode".to_string());
+                return Ok(false);
+            }
+            self.diff_options.wrap_lines = !self.diff_options.wrap_lines;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn toggle_word_diff(&mut self) -> Result<bool, String> {
+        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
+            self.diff_options.word_diff = !self.diff_options.word_diff;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn toggle_file_tree(&mut self) -> Result<bool, String> {
+        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
+            self.diff_options.file_tree = !self.diff_options.file_tree;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Expands or collapses the selected directory of the file tree.
+    /// Returns whether the selected file changed.
+    pub fn toggle_directory(&mut self) -> Result<bool, String> {
+        if self.active_view == ActiveView::Files && self.diff_options.file_tree {
+            if let Some(content) = &mut self.commit_state.content {
+                content.sync_tree();
+                content.tree.toggle(&content.diffs.items);
+                return Ok(content.select_tree_file());
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn toggle_blame(&mut self) -> Result<bool, String> {
+        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
+            self.diff_options.blame = !self.diff_options.blame;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Selects the commit with the given id in the graph panel.
+    /// Returns whether the selection changed.
+    pub fn select_commit(&mut self, oid: Oid) -> bool {
+        match self.graph_state.row_of_oid(Some(oid)) {
+            Some(row) => {
+                self.active_view = ActiveView::Graph;
+                self.graph_state.secondary_changed = false;
+                self.reflog_state = None;
+                if self.graph_state.selected == Some(row) {
+                    false
+                } else {
+                    self.graph_state.selected = Some(row);
+                    true
+                }
+            }
+            None => {
+                self.set_error(format!(
+                    "Commit {} is not contained in the graph.",
+                    &oid.to_string()[..7]
+                ));
+                false
+            }
+        }
+    }
+
+    pub fn toggle_syntax_highlight(&mut self) -> Result<bool, String> {
+        if self.active_view == ActiveView::Diff || self.active_view == ActiveView::Files {
+            self.diff_options.syntax_highlight = !self.diff_options.syntax_highlight;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn toggle_layout(&mut self) {
+        self.horizontal_split = !self.horizontal_split;
+    }
+
+    /// Focuses the panel at a screen position and selects the clicked row,
+    /// or starts dragging a border between panels
+    pub fn on_click(&mut self, column: u16, row: u16) -> Result<(bool, bool), String> {
+        if !self.accepts_mouse() {
+            return Ok((false, false));
+        }
+        self.layout.dragging = self.layout.border_at(column, row);
+        if self.layout.dragging.is_some() {
+            return Ok((false, false));
+        }
+        let (view, inner_row) = match self.layout.panel_at(column, row) {
+            Some(panel) => panel,
+            None => return Ok((false, false)),
+        };
+        self.active_view = view;
+        let inner_row = match inner_row {
+            Some(inner_row) => inner_row,
+            None => return Ok((false, false)),
+        };
+
+        match view {
+            ActiveView::Graph => {
+                let state = &mut self.graph_state;
+                let line = state.offset + inner_row;
+                if line < state.graph_lines.len() {
+                    let clicked = state.indices.partition_point(|idx| *idx <= line) - 1;
+                    if state.is_match(clicked) && state.selected != Some(clicked) {
+                        state.selected = Some(clicked);
+                        state.secondary_changed = false;
+                        if state.secondary_selected == state.selected {
+                            state.secondary_selected = None;
+                        }
+                        return Ok((true, false));
+                    }
+                }
+            }
+            ActiveView::Branches => {
+                if let Some(list) = &mut self.graph_state.branches {
+                    let index = list.state.offset + inner_row;
+                    if list
+                        .items
+                        .get(index)
+                        .is_some_and(|item| item.is_selectable())
+                    {
+                        list.state.select(Some(index));
+                    }
+                }
+            }
+            ActiveView::Reflog => {
+                if let Some(state) = &mut self.reflog_state {
+                    let list = &mut state.entries;
+                    let index = list.state.offset + inner_row;
+                    if index < list.items.len() && list.state.selected() != Some(index) {
+                        list.state.select(Some(index));
+                        self.show_reflog_entry()?;
+                        return Ok((false, true));
+                    }
+                }
+            }
+            ActiveView::Files => {
+                if let Some(content) = &mut self.commit_state.content {
+                    if self.diff_options.file_tree {
+                        content.sync_tree();
+                        let index = content.tree.state.offset + inner_row;
+                        if index < content.tree.rows.len() {
+                            // Clicking the selected directory expands or collapses it
+                            if content.tree.state.selected() == Some(index) {
+                                if content.tree.selected_file().is_none() {
+                                    content.tree.toggle(&content.diffs.items);
+                                }
+                            } else {
+                                content.tree.state.select(Some(index));
+                            }
+                            return Ok((false, content.select_tree_file()));
+                        }
+                        return Ok((false, false));
+                    }
+                    let list = &mut content.diffs;
+                    let index = list.state.offset + inner_row;
+                    if index < list.items.len() && list.state.selected() != Some(index) {
+                        list.state.select(Some(index));
+                        return Ok((false, true));
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok((false, false))
+    }
+
+    /// Drags a border between panels, if dragging was started by a click on it
+    pub fn on_drag(&mut self, column: u16, row: u16) {
+        self.layout.drag_to(column, row);
+    }
+
+    pub fn on_release(&mut self) {
+        self.layout.dragging = None;
+    }
+
+    /// Scrolls the panel at a screen position, without focusing it
+    pub fn on_scroll(&mut self, column: u16, row: u16, down: bool) -> Result<(bool, bool), String> {
+        let view = match self.active_view {
+            ActiveView::Help(_) | ActiveView::Models => self.active_view,
+            _ if self.accepts_mouse() => match self.layout.panel_at(column, row) {
+                Some((view, _)) => view,
+                None => return Ok((false, false)),
+            },
+            _ => return Ok((false, false)),
+        };
+        let steps = match view {
+            ActiveView::Commit | ActiveView::Diff | ActiveView::Help(_) => MOUSE_SCROLL_LINES,
+            _ => 1,
+        };
+
+        let active_view = std::mem::replace(&mut self.active_view, view);
+        let mut result = (false, false);
+        for _ in 0..steps {
+            let (rd, rf) = if down {
+                self.on_down(false, false)?
+            } else {
+                self.on_up(false, false)?
+            };
+            result = (result.0 || rd, result.1 || rf);
+        }
+        if let ActiveView::Help(_) = view {
+        } else {
+            self.active_view = active_view;
+        }
+        Ok(result)
+    }
+
+    /// Whether mouse clicks go to the panels, i.e. no dialog or fullscreen view is open
+    fn accepts_mouse(&self) -> bool {
+        matches!(
+            self.active_view,
+            ActiveView::Branches
+                | ActiveView::Graph
+                | ActiveView::Reflog
+                | ActiveView::Commit
+                | ActiveView::Files
+                | ActiveView::Diff
+        ) && self.error_message.is_none()
+            && self.conflicts.is_none()
+    }
+
+    pub fn toggle_branches(&mut self) {
+        self.show_branches = !self.show_branches;
+    }
+
+    pub fn show_help(&mut self) {
+        if let ActiveView::Help(_) = self.active_view {
         } else {
             let mut temp = ActiveView::Help(0);
             std::mem::swap(&mut temp, &mut self.active_view);
@@ -712,88 +2211,174 @@ impl App {
     pub fn selection_changed(&mut self) -> Result<(), String> {
         self.reload_diff_message()?;
         let _reload_file = self.reload_diff_files()?;
+        if !self.filter.paths.is_empty() {
+            self.file_changed(true)?;
+        }
         Ok(())
     }
 
-    pub fn reload_diff_message(&mut self) -> Result<(), String> {
-        if let Some(graph) = &self.graph_state.graph {
-            self.commit_state.content =
-                if let Some((info, idx)) = self.graph_state.selected.and_then(move |sel_idx| {
-                    graph.commits.get(sel_idx).map(|commit| (commit, sel_idx))
-                }) {
-                    let commit = graph
-                        .repository
-                        .find_commit(info.oid)
-                        .map_err(|err| err.message().to_string())?;
-
-                    let head_idx = graph.indices.get(&graph.head.oid);
-                    let head = if head_idx == Some(&idx) {
-                        Some(&graph.head)
-                    } else {
-                        None
-                    };
-
-                    let hash_color = if self.color { Some(HASH_COLOR) } else { None };
-                    let branches = format_branches(graph, info, head, self.color);
-                    let message_fmt = crate::util::format::format(&commit, branches, hash_color);
-
-                    let compare_to = if let Some(sel) = self.graph_state.secondary_selected {
-                        let sec_selected_info = graph.commits.get(sel);
-                        if let Some(info) = sec_selected_info {
-                            Some(
-                                graph
-                                    .repository
-                                    .find_commit(info.oid)
-                                    .map_err(|err| err.message().to_string())?,
-                            )
-                        } else {
-                            commit.parent(0).ok()
-                        }
-                    } else {
-                        commit.parent(0).ok()
-                    };
-                    let comp_oid = compare_to.as_ref().map(|c| c.id());
-
-                    Some(CommitViewInfo::new(
-                        message_fmt,
-                        StatefulList::default(),
-                        info.oid,
-                        comp_oid.unwrap_or_else(Oid::zero),
-                    ))
-                } else {
-                    None
+    /// Reloads the commit, files and diff panels for the selected commit.
+    /// If the commit is the same as before, keeps the selected file and the scroll positions.
+    fn refresh_selection(&mut self) -> Result<(), String> {
+        let old = self.commit_state.content.as_ref().map(|content| {
+            (
+                (content.oid, content.pseudo),
+                content
+                    .diffs
+                    .state
+                    .selected()
+                    .map(|idx| content.diffs.items[idx].file.clone()),
+                content.scroll,
+            )
+        });
+        self.selection_changed()?;
+        let mut kept_file = false;
+        if let Some(content) = &mut self.commit_state.content {
+            let (file, scroll) = match old {
+                Some((commit, file, scroll)) if commit == (content.oid, content.pseudo) => {
+                    (file, scroll)
                 }
+                _ => (None, 0),
+            };
+            content.scroll = scroll;
+            if let Some(idx) = file.and_then(|file| {
+                content
+                    .diffs
+                    .items
+                    .iter()
+                    .position(|item| item.file == file)
+            }) {
+                content.diffs.state.select(Some(idx));
+                kept_file = true;
+            }
         }
-        Ok(())
+        self.file_changed(!kept_file)
     }
 
-    pub fn reload_diff_files(&mut self) -> Result<bool, String> {
+    pub fn reload_diff_message(&mut self) -> Result<(), String> {
         if let Some(graph) = &self.graph_state.graph {
-            if let Some(content) = &mut self.commit_state.content {
+            self.commit_state.content = if let Some(pseudo) = self.graph_state.selected_pseudo() {
+                let compare_oid = match pseudo {
+                    PseudoCommit::Staged => graph.head.oid,
+                    PseudoCommit::Unstaged | PseudoCommit::Stash(_) => Oid::zero(),
+                };
+                let mut info = CommitViewInfo::new(
+                    vec![
+                        pseudo.label().to_string(),
+                        String::new(),
+                        String::new(),
+                        format!("    {}", pseudo.description()),
+                    ],
+                    StatefulList::default(),
+                    Oid::zero(),
+                    compare_oid,
+                );
+                info.pseudo = Some(pseudo);
+                Some(info)
+            } else if let Some((info, idx)) = self
+                .graph_state
+                .selected_commit()
+                .and_then(move |sel_idx| graph.commits.get(sel_idx).map(|commit| (commit, sel_idx)))
+            {
                 let commit = graph
                     .repository
-                    .find_commit(content.oid)
+                    .find_commit(info.oid)
                     .map_err(|err| err.message().to_string())?;
 
-                let compare_to = if content.compare_oid.is_zero() {
-                    None
+                let head_idx = graph.indices.get(&graph.head.oid);
+                let head = if head_idx == Some(&idx) {
+                    Some(&graph.head)
                 } else {
+                    None
+                };
+
+                let hash_color = self.hash_color();
+                let branches = format_branches(graph, info, head, self.color);
+                let message_fmt = crate::util::format::format(&commit, branches, hash_color);
+
+                let compare_to = if let Some(sel) = self.graph_state.secondary_selected_commit() {
+                    let sec_selected_info = graph.commits.get(sel);
+                    if let Some(info) = sec_selected_info {
+                        Some(
+                            graph
+                                .repository
+                                .find_commit(info.oid)
+                                .map_err(|err| err.message().to_string())?,
+                        )
+                    } else {
+                        commit.parent(0).ok()
+                    }
+                } else if let Some((_, oid)) = &self.compare_target {
                     Some(
                         graph
                             .repository
-                            .find_commit(content.compare_oid)
+                            .find_commit(*oid)
                             .map_err(|err| err.message().to_string())?,
                     )
+                } else {
+                    commit.parent(0).ok()
                 };
+                let comp_oid = compare_to.as_ref().map(|c| c.id());
+
+                Some(CommitViewInfo::new(
+                    message_fmt,
+                    StatefulList::default(),
+                    info.oid,
+                    comp_oid.unwrap_or_else(Oid::zero),
+                ))
+            } else {
+                None
+            }
+        }
+        Ok(())
+    }
+
+    pub fn reload_diff_files(&mut self) -> Result<bool, String> {
+        if let Some(graph) = &self.graph_state.graph {
+            if let Some(content) = &mut self.commit_state.content {
+                let diff = create_diff(
+                    &graph.repository,
+                    content.oid,
+                    content.compare_oid,
+                    content.pseudo,
+                    &mut GDiffOptions::new(),
+                )?;
 
-                let diffs = get_diff_files(graph, compare_to.as_ref(), &commit)?;
+                let diffs = get_diff_files(&diff)?;
 
-                content.diffs = StatefulList::with_items(diffs)
+                content.diffs = StatefulList::with_items(diffs);
+
+                if let Some(path) = self
+                    .path_history
+                    .commits
+                    .get(&content.oid)
+                    .or_else(|| self.filter.paths.first())
+                {
+                    let dir = format!("{}/", path.trim_end_matches('/'));
+                    let selected = content
+                        .diffs
+                        .items
+                        .iter()
+                        .position(|item| &item.file == path || item.file.starts_with(&dir));
+                    content.diffs.state.select(selected);
+                }
             }
         }
         Ok(true)
     }
 
+    /// Selects the first file in the files panel, and shows its diff
+    fn select_first_file(&mut self) -> Result<(), String> {
+        if let Some(content) = &mut self.commit_state.content {
+            if content.diffs.state.selected().is_none() && !content.diffs.items.is_empty() {
+                let first = content.first_file(self.diff_options.file_tree);
+                content.diffs.state.select(Some(first));
+                self.file_changed(true)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn clear_file_diff(&mut self) {
         if let Some(content) = &mut self.diff_state.content {
             content.diffs.clear();
@@ -802,73 +2387,100 @@ impl App {
     }
 
     pub fn file_changed(&mut self, reset_scroll: bool) -> Result<(), String> {
+        let mut blame_error = None;
         if let (Some(graph), Some(state)) = (&self.graph_state.graph, &self.commit_state.content) {
-            self.diff_state.content = if let Some((info, sel_index)) = self
-                .graph_state
-                .selected
-                .and_then(move |sel_idx| graph.commits.get(sel_idx))
-                .and_then(|info| {
-                    state
-                        .diffs
-                        .state
-                        .selected()
-                        .map(|sel_index| (info, sel_index))
-                }) {
-                let commit = graph
-                    .repository
-                    .find_commit(info.oid)
-                    .map_err(|err| err.message().to_string())?;
-
-                let compare_to = if let Some(sel) = self.graph_state.secondary_selected {
-                    let sec_selected_info = graph.commits.get(sel);
-                    if let Some(info) = sec_selected_info {
-                        Some(
-                            graph
-                                .repository
-                                .find_commit(info.oid)
-                                .map_err(|err| err.message().to_string())?,
-                        )
-                    } else {
-                        commit.parent(0).ok()
-                    }
-                } else {
-                    commit.parent(0).ok()
-                };
-                let comp_oid = compare_to.as_ref().map(|c| c.id());
-
+            self.diff_state.content = if let Some(sel_index) = state.diffs.state.selected() {
                 let selection = &state.diffs.items[sel_index];
 
+                let mut opts = GDiffOptions::new();
+                opts.context_lines(self.diff_options.context_lines);
+                opts.indent_heuristic(true);
+                opts.pathspec(&selection.file);
+                opts.disable_pathspec_match(true);
+                let diff = create_diff(
+                    &graph.repository,
+                    state.oid,
+                    state.compare_oid,
+                    state.pseudo,
+                    &mut opts,
+                )?;
+
                 let diffs = get_file_diffs(
-                    graph,
-                    compare_to.as_ref(),
-                    &commit,
-                    &selection.file,
+                    &graph.repository,
+                    &diff,
+                    revision_names(state.oid, state.compare_oid, state.pseudo),
+                    state.pseudo == Some(PseudoCommit::Unstaged),
                     &self.diff_options,
                     &self.settings.tab_spaces,
                 )?;
 
-                let highlighted = if self.color
-                    && self.diff_options.syntax_highlight
-                    && self.diff_options.diff_mode != DiffMode::Diff
-                    && diffs.len() == 2
-                {
-                    PathBuf::from(&selection.file)
-                        .extension()
-                        .and_then(|ext| ext.to_str().and_then(|ext| highlight(&diffs[1].0, ext)))
+                let highlighted = if !self.color || !self.diff_options.syntax_highlight {
+                    None
+                } else if self.diff_options.diff_mode.is_patch() {
+                    highlight_patch(
+                        &graph.repository,
+                        &diff,
+                        &diffs,
+                        &selection.file,
+                        state.pseudo == Some(PseudoCommit::Unstaged),
+                        &self.settings.tab_spaces,
+                        &mut self.diff_state.highlight_cache,
+                    )
+                } else if diffs.len() == 2 {
+                    highlight(&diffs[1].0, &selection.file)
                 } else {
                     None
                 };
 
-                let mut info = DiffViewInfo::new(
-                    diffs,
-                    highlighted,
-                    info.oid,
-                    comp_oid.unwrap_or_else(Oid::zero),
-                );
+                let blame = if self.diff_options.blame && !self.diff_options.diff_mode.is_patch() {
+                    let (oid, file) = if self.diff_options.diff_mode == DiffMode::New {
+                        (state.oid, diff.deltas().next().map(|d| d.new_file()))
+                    } else {
+                        (
+                            state.compare_oid,
+                            diff.deltas().next().map(|d| d.old_file()),
+                        )
+                    };
+                    // Only committed versions can be blamed
+                    let committed = match state.pseudo {
+                        Some(PseudoCommit::Staged) => self.diff_options.diff_mode == DiffMode::Old,
+                        Some(PseudoCommit::Unstaged) | Some(PseudoCommit::Stash(_)) => false,
+                        None => true,
+                    };
+                    // The file does not exist on this side, e.g. an added file in old mode
+                    let file = file.filter(|file| !file.id().is_zero());
+                    match file.and_then(|file| file.path()) {
+                        Some(path) if committed && !oid.is_zero() && diffs.len() > 1 => {
+                            match get_blame(&graph.repository, oid, path) {
+                                Ok(lines) => Some(BlameInfo {
+                                    lines,
+                                    header_lines: diffs[0].0.lines().count(),
+                                    cursor: 0,
+                                }),
+                                Err(err) => {
+                                    blame_error = Some(format!("Unable to blame file\n{}", err));
+                                    None
+                                }
+                            }
+                        }
+                        _ => None,
+                    }
+                } else {
+                    None
+                };
+
+                let mut info = DiffViewInfo::new(diffs, highlighted, state.oid, state.compare_oid);
+                info.pseudo = state.pseudo;
+                info.blame = blame;
 
                 if !reset_scroll {
                     if let Some(diff_state) = &self.diff_state.content {
                         info.scroll = diff_state.scroll;
+                        if let (Some(blame), Some(old_blame)) = (&mut info.blame, &diff_state.blame)
+                        {
+                            blame.cursor =
+                                old_blame.cursor.min(blame.lines.len().saturating_sub(1));
+                        }
                     }
                 }
 
@@ -877,35 +2489,224 @@ impl App {
                 None
             }
         }
+        if let Some(err) = blame_error {
+            self.set_error(err);
+        }
         Ok(())
     }
 
     pub fn set_error(&mut self, msg: String) {
         self.error_message = Some(msg);
     }
+    /// Closes the error or conflicts dialog
     pub fn clear_error(&mut self) {
         self.error_message = None;
+        self.conflicts = None;
+    }
+
+    pub fn set_status(&mut self, msg: String) {
+        self.status_message = Some((msg, Instant::now()));
+    }
+    /// The status message, if it was set recently
+    pub fn status(&self) -> Option<&str> {
+        self.status_message
+            .as_ref()
+            .filter(|(_, time)| time.elapsed() < STATUS_DURATION)
+            .map(|(msg, _)| &msg[..])
+    }
+}
+
+/// Short names of the old and new revision of a diff
+pub fn revision_names(
+    oid: Oid,
+    compare_oid: Oid,
+    pseudo: Option<PseudoCommit>,
+) -> (String, String) {
+    match pseudo {
+        Some(PseudoCommit::Staged) => (
+            compare_oid.to_string()[..7].to_string(),
+            "index".to_string(),
+        ),
+        Some(PseudoCommit::Unstaged) => ("index".to_string(), "working tree".to_string()),
+        Some(PseudoCommit::Stash(index)) => (
+            compare_oid.to_string()[..7].to_string(),
+            format!("stash@{{{}}}", index),
+        ),
+        None => (
+            compare_oid.to_string()[..7].to_string(),
+            oid.to_string()[..7].to_string(),
+        ),
+    }
+}
+
+/// Creates the diff between two commits, or the diff of uncommitted changes
+fn create_diff<'a>(
+    repository: &'a Repository,
+    oid: Oid,
+    compare_oid: Oid,
+    pseudo: Option<PseudoCommit>,
+    opts: &mut GDiffOptions,
+) -> Result<Diff<'a>, String> {
+    let tree = |oid: Oid| {
+        if oid.is_zero() {
+            Ok(None)
+        } else {
+            repository
+                .find_commit(oid)
+                .and_then(|commit| commit.tree())
+                .map(Some)
+                .map_err(|err| err.message().to_string())
+        }
+    };
+    match pseudo {
+        Some(PseudoCommit::Staged) => {
+            repository.diff_tree_to_index(tree(compare_oid)?.as_ref(), None, Some(opts))
+        }
+        Some(PseudoCommit::Unstaged) => {
+            opts.include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+            repository.diff_index_to_workdir(None, Some(opts))
+        }
+        Some(PseudoCommit::Stash(_)) => {
+            let mut diff = repository
+                .diff_tree_to_tree(tree(compare_oid)?.as_ref(), tree(oid)?.as_ref(), Some(opts))
+                .map_err(|err| err.message().to_string())?;
+            // Untracked files are stored in a third parent, without history
+            let untracked = repository
+                .find_commit(oid)
+                .and_then(|commit| commit.parent_id(2))
+                .ok();
+            if let Some(untracked) = untracked {
+                let untracked_diff = repository
+                    .diff_tree_to_tree(None, tree(untracked)?.as_ref(), Some(opts))
+                    .map_err(|err| err.message().to_string())?;
+                diff.merge(&untracked_diff)
+                    .map_err(|err| err.message().to_string())?;
+            }
+            Ok(diff)
+        }
+        None => repository.diff_tree_to_tree(
+            tree(compare_oid)?.as_ref(),
+            tree(oid)?.as_ref(),
+            Some(opts),
+        ),
+    }
+    .map_err(|err| err.message().to_string())
+}
+
+/// Collects the uncommitted changes of the repository, shown as pseudo-commits
+pub fn get_pseudo_commits(repository: &Repository) -> Result<Vec<PseudoCommit>, String> {
+    if repository.is_bare() {
+        return Ok(vec![]);
     }
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true).include_ignored(false);
+    let statuses = repository
+        .statuses(Some(&mut opts))
+        .map_err(|err| err.message().to_string())?;
+
+    let staged = Status::INDEX_NEW
+        | Status::INDEX_MODIFIED
+        | Status::INDEX_DELETED
+        | Status::INDEX_RENAMED
+        | Status::INDEX_TYPECHANGE;
+    // Conflicts of a cherry-pick, revert or merge are resolved in the working tree
+    let unstaged = Status::WT_NEW
+        | Status::WT_MODIFIED
+        | Status::WT_DELETED
+        | Status::WT_RENAMED
+        | Status::WT_TYPECHANGE
+        | Status::CONFLICTED;
+
+    let mut pseudo = vec![];
+    if statuses
+        .iter()
+        .any(|entry| entry.status().intersects(unstaged))
+    {
+        pseudo.push(PseudoCommit::Unstaged);
+    }
+    if statuses
+        .iter()
+        .any(|entry| entry.status().intersects(staged))
+    {
+        pseudo.push(PseudoCommit::Staged);
+    }
+    Ok(pseudo)
 }
 
-fn get_diff_files(
+/// Creates graph and text lines for the pseudo-commits, placed in HEAD's column
+fn pseudo_commit_lines(
     graph: &GitGraph,
-    old: Option<&Commit>,
-    new: &Commit,
-) -> Result<Vec<DiffItem>, String> {
-    let mut diffs = vec![];
-    let diff = graph
-        .repository
-        .diff_tree_to_tree(
-            old.map(|c| c.tree())
-                .map_or(Ok(None), |v| v.map(Some))
-                .map_err(|err| err.message().to_string())?
-                .as_ref(),
-            Some(&new.tree().map_err(|err| err.message().to_string())?),
-            None,
-        )
+    pseudo_commits: &[PseudoCommit],
+    hash_color: Option<u8>,
+) -> (Vec<String>, Vec<String>) {
+    use yansi::Paint;
+
+    let (column, branch_color) = graph
+        .indices
+        .get(&graph.head.oid)
+        .and_then(|idx| graph.commits[*idx].branch_trace)
+        .map(|branch| {
+            let visual = &graph.all_branches[branch].visual;
+            (visual.column.unwrap_or(0), visual.term_color)
+        })
+        .unwrap_or((0, 7));
+
+    pseudo_commits
+        .iter()
+        .map(|pseudo| {
+            let indent = " ".repeat(2 * column);
+            if let Some(hash_color) = hash_color {
+                (
+                    format!("{}{}", indent, PSEUDO_COMMIT_CHAR.fixed(branch_color)),
+                    pseudo.label().fixed(hash_color).to_string(),
+                )
+            } else {
+                (
+                    format!("{}{}", indent, PSEUDO_COMMIT_CHAR),
+                    pseudo.label().to_string(),
+                )
+            }
+        })
+        .unzip()
+}
+
+/// Annotates each line of a file at the given commit with the commit that last changed it
+fn get_blame(repository: &Repository, oid: Oid, path: &Path) -> Result<Vec<(Oid, String)>, String> {
+    let mut opts = BlameOptions::new();
+    opts.newest_commit(oid);
+    let blame = repository
+        .blame_file(path, Some(&mut opts))
         .map_err(|err| err.message().to_string())?;
 
+    let mut lines = vec![];
+    for hunk in blame.iter() {
+        let id = hunk.final_commit_id();
+        let signature = hunk.final_signature();
+        let author: String = signature
+            .name()
+            .unwrap_or("")
+            .chars()
+            .take(BLAME_AUTHOR_WIDTH)
+            .collect();
+        let annotation = format!(
+            "{} {:<width$} {}",
+            &id.to_string()[..7],
+            author,
+            format_date(signature.when(), "%Y-%m-%d"),
+            width = BLAME_AUTHOR_WIDTH,
+        );
+        for _ in 0..hunk.lines_in_hunk() {
+            lines.push((id, annotation.clone()));
+        }
+    }
+    Ok(lines)
+}
+
+fn get_diff_files(diff: &Diff) -> Result<Vec<DiffItem>, String> {
+    let mut diffs = vec![];
+
     let mut diff_err = Ok(());
     diff.print(DiffFormat::NameStatus, |d, _h, l| {
         let content =
@@ -931,38 +2732,39 @@ fn get_diff_files(
 
     diff_err?;
 
+    // Conflicted files are not printed, but resolved in the working tree
+    for delta in diff.deltas() {
+        if delta.status() == Delta::Conflicted {
+            diffs.push(DiffItem {
+                file: delta
+                    .new_file()
+                    .path()
+                    .and_then(|p| p.to_str())
+                    .unwrap_or("")
+                    .to_string(),
+                diff_type: DiffType::Modified,
+            });
+        }
+    }
+
     Ok(diffs)
 }
 
+/// Creates the lines of a file's diff, or its old or new version.
+/// `names` are the names of the old and new revision, used in messages.
+/// With `workdir`, the new version is read from the working tree.
 fn get_file_diffs(
-    graph: &GitGraph,
-    old: Option<&Commit>,
-    new: &Commit,
-    path: &str,
+    repository: &Repository,
+    diff: &Diff,
+    names: (String, String),
+    workdir: bool,
     options: &DiffOptions,
     tab_spaces: &str,
 ) -> Result<DiffLines, String> {
     let mut diffs = vec![];
-    let mut opts = GDiffOptions::new();
-    opts.context_lines(options.context_lines);
-    opts.indent_heuristic(true);
-    opts.pathspec(path);
-    opts.disable_pathspec_match(true);
-    let diff = graph
-        .repository
-        .diff_tree_to_tree(
-            old.map(|c| c.tree())
-                .map_or(Ok(None), |v| v.map(Some))
-                .map_err(|err| err.message().to_string())?
-                .as_ref(),
-            Some(&new.tree().map_err(|err| err.message().to_string())?),
-            Some(&mut opts),
-        )
-        .map_err(|err| err.message().to_string())?;
-
     let mut diff_error = Ok(());
 
-    if options.diff_mode == DiffMode::Diff {
+    if options.diff_mode.is_patch() {
         diff.print(DiffFormat::Patch, |d, h, l| {
             diffs.push((
                 print_diff_line(&d, &h, &l).replace('\t', tab_spaces),
@@ -973,29 +2775,44 @@ fn get_file_diffs(
         })
         .map_err(|err| err.message().to_string())?;
     } else {
+        let name = if options.diff_mode == DiffMode::New {
+            &names.1
+        } else {
+            &names.0
+        };
         match diff.print(DiffFormat::PatchHeader, |d, _h, l| {
-            let (blob_oid, oid) = if options.diff_mode == DiffMode::New {
-                (d.new_file().id(), new.id())
-            } else {
-                (
-                    d.old_file().id(),
-                    old.map(|c| c.id()).unwrap_or_else(Oid::zero),
-                )
-            };
-
             let line = std::str::from_utf8(l.content())
                 .unwrap_or("Invalid UTF8 character.")
                 .replace('\t', tab_spaces);
             diffs.push((line, None, None));
 
+            if workdir && options.diff_mode == DiffMode::New {
+                let path = repository
+                    .workdir()
+                    .zip(d.new_file().path())
+                    .map(|(dir, path)| dir.join(path));
+                match path.and_then(|path| std::fs::read(path).ok()) {
+                    Some(content) => {
+                        let text = std::str::from_utf8(&content)
+                            .map_err(|err| err.to_string())
+                            .unwrap_or("Invalid UTF8 character.");
+                        diffs.push((text.replace('\t', tab_spaces), None, None));
+                    }
+                    None => diffs.push((format!("File does not exist in {}", name), None, None)),
+                }
+                return true;
+            }
+
+            let blob_oid = if options.diff_mode == DiffMode::New {
+                d.new_file().id()
+            } else {
+                d.old_file().id()
+            };
+
             if blob_oid.is_zero() {
-                diffs.push((
-                    format!("File does not exist in {}", &oid.to_string()[..7]),
-                    None,
-                    None,
-                ))
+                diffs.push((format!("File does not exist in {}", name), None, None))
             } else {
-                let blob = match graph.repository.find_blob(blob_oid) {
+                let blob = match repository.find_blob(blob_oid) {
                     Ok(blob) => blob,
                     Err(err) => {
                         diff_error = Err(err.to_string());
@@ -1011,24 +2828,79 @@ fn get_file_diffs(
             true
         }) {
             Ok(_) => {}
-            Err(_) => {
-                let oid = if options.diff_mode == DiffMode::New {
-                    new.id()
-                } else {
-                    old.map(|c| c.id()).unwrap_or_else(Oid::zero)
-                };
-                diffs.push((
-                    format!("File does not exist in {}", &oid.to_string()[..7]),
-                    None,
-                    None,
-                ))
-            }
+            Err(_) => diffs.push((format!("File does not exist in {}", name), None, None)),
         };
     }
     diff_error?;
     Ok(diffs)
 }
 
+/// Syntax highlighting of each line of a patch. Removed lines are highlighted as part of
+/// the old file, added and context lines as part of the new file, so that constructs
+/// spanning multiple lines are highlighted correctly.
+fn highlight_patch(
+    repository: &Repository,
+    diff: &Diff,
+    diffs: &DiffLines,
+    path: &str,
+    workdir: bool,
+    tab_spaces: &str,
+    cache: &mut HighlightCache,
+) -> Option<Vec<Vec<(SynStyle, String)>>> {
+    let delta = diff.deltas().next()?;
+    let mut highlighted = vec![vec![]; diffs.len()];
+
+    for (new, file) in [(false, delta.old_file()), (true, delta.new_file())] {
+        let (oid, content) = if new && workdir {
+            let path = repository
+                .workdir()
+                .zip(file.path())
+                .map(|(dir, path)| dir.join(path));
+            match path.and_then(|path| std::fs::read(path).ok()) {
+                Some(content) => match Oid::hash_object(ObjectType::Blob, &content) {
+                    Ok(oid) => (oid, Some(content)),
+                    Err(_) => continue,
+                },
+                None => continue,
+            }
+        } else if file.id().is_zero() {
+            continue;
+        } else {
+            (file.id(), None)
+        };
+
+        let text = || {
+            let content = content.or_else(|| {
+                let blob = repository.find_blob(oid).ok()?;
+                Some(blob.content().to_vec())
+            })?;
+            let text = String::from_utf8(content).ok()?;
+            Some(text.replace('\t', tab_spaces))
+        };
+        let file = match cache.get(oid, path, text) {
+            Some(file) => file,
+            None => continue,
+        };
+
+        for (idx, (line, old_ln, new_ln)) in diffs.iter().enumerate() {
+            let number = match line.chars().next() {
+                Some('-') if !new => *old_ln,
+                Some('+') | Some(' ') if new => *new_ln,
+                _ => None,
+            };
+            if let Some(spans) = number.and_then(|number| file.line(number as usize)) {
+                // Skip lines that differ from the file, e.g. due to line ending conversion
+                let content = line[1..].trim_end_matches(['\n', '\r']);
+                let joined: String = spans.iter().map(|(_, text)| text.as_str()).collect();
+                if joined == content {
+                    highlighted[idx] = spans.to_vec();
+                }
+            }
+        }
+    }
+    Some(highlighted)
+}
+
 fn print_diff_line(_delta: &DiffDelta, _hunk: &Option<DiffHunk>, line: &DiffLine) -> String {
     let mut out = String::new();
     match line.origin() {
@@ -1045,7 +2917,7 @@ fn print_diff_line(_delta: &DiffDelta, _hunk: &Option<DiffHunk>, line: &DiffLine
     out
 }
 
-fn get_branches(graph: &GitGraph) -> Vec<BranchItem> {
+fn get_branches(graph: &GitGraph, stashes: Vec<(usize, String, Oid)>) -> Vec<BranchItem> {
     let mut branches = Vec::new();
 
     branches.push(BranchItem::new(
@@ -1117,5 +2989,20 @@ fn get_branches(graph: &GitGraph) -> Vec<BranchItem> {
 
     branches.extend(tags.into_iter().map(|bt| bt.0));
 
+    branches.push(BranchItem::new(
+        "Stashes".to_string(),
+        None,
+        7,
+        BranchItemType::Heading,
+    ));
+    for (index, message, oid) in stashes {
+        branches.push(BranchItem::new(
+            format!("stash@{{{}}}: {}", index, message),
+            None,
+            7,
+            BranchItemType::Stash { index, oid },
+        ));
+    }
+
     branches
 }
@@ -0,0 +1,74 @@
+use crate::app::DiffOptions;
+use crate::util::syntax_highlight::DEFAULT_THEME;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// User configuration, read from `config.toml` in the git-graph config directory.
+///
+/// Missing entries use the defaults. Command line options override the entries.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// When to use colors. One of [auto|always|never]
+    pub color: String,
+    /// Graph style. One of [normal/thin|round|bold|double|ascii]
+    pub style: String,
+    /// Commit format. One of [oneline|short|medium|full|"<string>"]
+    pub format: String,
+    /// Show remote branches
+    pub include_remote: bool,
+    /// Tab width for display in diffs
+    pub tab_width: usize,
+    /// Graph panel on the left (true) or on top (false)
+    pub horizontal_split: bool,
+    /// Show the branch list
+    pub show_branches: bool,
+    /// Capture the mouse. Disable to select text with the mouse
+    pub mouse: bool,
+    /// Command to copy to the clipboard, reading from stdin, e.g. "xclip -selection clipboard".
+    /// If empty, asks the terminal to copy (OSC 52), which also works over SSH
+    pub clipboard_command: String,
+    /// Color theme. One of [dark|light|high-contrast], or the name of a file
+    /// `<name>.toml` in the `themes` directory next to this file
+    pub theme: String,
+    /// Theme for syntax highlighting, by name. Additional `.tmTheme` files and
+    /// `.sublime-syntax` definitions are loaded from the `syntax` directory next to this file
+    pub syntax_theme: String,
+    /// Initial options of the diff panels
+    pub diff: DiffOptions,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            color: "auto".to_string(),
+            style: "round".to_string(),
+            format: "oneline".to_string(),
+            include_remote: true,
+            tab_width: 4,
+            horizontal_split: true,
+            show_branches: false,
+            mouse: true,
+            clipboard_command: String::new(),
+            theme: "dark".to_string(),
+            syntax_theme: DEFAULT_THEME.to_string(),
+            diff: DiffOptions::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from a TOML file, or the defaults if the file does not exist
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        toml::from_str(&content)
+            .map_err(|err| format!("Error in config file {}\n{}", path.display(), err))
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string_pretty(self).map_err(|err| err.to_string())
+    }
+}
@@ -1,5 +1,5 @@
 use crate::app::App;
-use git2::Repository;
+use git2::{Oid, Repository};
 use std::io::Error;
 use std::path::PathBuf;
 use tui::widgets::ListState;
@@ -156,3 +156,143 @@ impl<'a> FileDialog<'a> {
         self.error_message = None;
     }
 }
+
+/// Action on the repository, to be confirmed or completed by user input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepoAction {
+    CheckoutCommit(Oid),
+    CheckoutBranch(String),
+    DeleteBranch(String),
+    CreateBranch(Oid),
+    CreateTag(Oid),
+    CreateAnnotatedTag(Oid),
+    /// Message for the annotated tag with the given name
+    TagMessage(Oid, String),
+    /// Stashes by their index, as in `stash@{index}`
+    ApplyStash(usize),
+    PopStash(usize),
+    DropStash(usize),
+    /// Export of the graph to a file, without changing the repository
+    ExportGraph,
+    /// Revision to compare the selected commit to, without changing the repository
+    CompareTo,
+    CherryPick(Oid),
+    Revert(Oid),
+    /// Merge commits with their number of parents, asking for the mainline parent
+    CherryPickMerge(Oid, usize),
+    RevertMerge(Oid, usize),
+}
+
+/// Dialog to confirm a `RepoAction`, or to enter text required by it
+pub struct ActionDialog {
+    pub action: RepoAction,
+    /// Text entered by the user, or `None` for a confirmation without input
+    pub input: Option<String>,
+}
+
+impl ActionDialog {
+    pub fn new(action: RepoAction) -> Self {
+        let input = match action {
+            RepoAction::CheckoutCommit(_)
+            | RepoAction::CheckoutBranch(_)
+            | RepoAction::DeleteBranch(_)
+            | RepoAction::ApplyStash(_)
+            | RepoAction::PopStash(_)
+            | RepoAction::DropStash(_)
+            | RepoAction::CherryPick(_)
+            | RepoAction::Revert(_) => None,
+            RepoAction::CherryPickMerge(_, _) | RepoAction::RevertMerge(_, _) => {
+                Some("1".to_string())
+            }
+            RepoAction::CreateBranch(_)
+            | RepoAction::CreateTag(_)
+            | RepoAction::CreateAnnotatedTag(_)
+            | RepoAction::TagMessage(_, _) => Some(String::new()),
+            RepoAction::ExportGraph => Some("graph.svg".to_string()),
+            RepoAction::CompareTo => Some(String::new()),
+        };
+        Self { action, input }
+    }
+
+    pub fn title(&self) -> String {
+        let short = |oid: &Oid| oid.to_string()[..7].to_string();
+        let title = match &self.action {
+            RepoAction::CheckoutCommit(_) | RepoAction::CheckoutBranch(_) => "Checkout".to_string(),
+            RepoAction::DeleteBranch(_) => "Delete branch".to_string(),
+            RepoAction::ApplyStash(_) => "Apply stash".to_string(),
+            RepoAction::PopStash(_) => "Pop stash".to_string(),
+            RepoAction::DropStash(_) => "Drop stash".to_string(),
+            RepoAction::CreateBranch(oid) => format!("Create branch at {}", short(oid)),
+            RepoAction::CreateTag(oid) => format!("Create tag at {}", short(oid)),
+            RepoAction::CreateAnnotatedTag(oid) => {
+                format!("Create annotated tag at {}", short(oid))
+            }
+            RepoAction::TagMessage(_, name) => format!("Message for tag '{}'", name),
+            RepoAction::ExportGraph => "Export graph".to_string(),
+            RepoAction::CompareTo => "Compare to (empty to reset)".to_string(),
+            RepoAction::CherryPick(_) | RepoAction::CherryPickMerge(_, _) => {
+                "Cherry-pick".to_string()
+            }
+            RepoAction::Revert(_) | RepoAction::RevertMerge(_, _) => "Revert".to_string(),
+        };
+        format!(" {} - Confirm with Enter, abort with Esc ", title)
+    }
+
+    /// The text to show in the dialog
+    pub fn text(&self) -> String {
+        if let Some(input) = &self.input {
+            let label = match &self.action {
+                RepoAction::TagMessage(_, _) => "Message",
+                RepoAction::ExportGraph => "File (.svg or .html)",
+                RepoAction::CompareTo => "Branch, tag or commit",
+                RepoAction::CherryPickMerge(oid, parents)
+                | RepoAction::RevertMerge(oid, parents) => {
+                    return format!(
+                        "{} is a merge. Mainline parent (1-{}): {}_",
+                        &oid.to_string()[..7],
+                        parents,
+                        input
+                    );
+                }
+                _ => "Name",
+            };
+            return format!("{}: {}_", label, input);
+        }
+        match &self.action {
+            RepoAction::CheckoutCommit(oid) => format!(
+                "Checkout commit {} as detached HEAD?",
+                &oid.to_string()[..7]
+            ),
+            RepoAction::CheckoutBranch(name) => format!("Checkout branch '{}'?", name),
+            RepoAction::DeleteBranch(name) => format!("Delete branch '{}'?", name),
+            RepoAction::ApplyStash(index) => {
+                format!("Apply stash@{{{}}} to the working tree?", index)
+            }
+            RepoAction::PopStash(index) => format!(
+                "Apply stash@{{{}}} to the working tree, and drop it if successful?",
+                index
+            ),
+            RepoAction::DropStash(index) => format!("Drop stash@{{{}}}?", index),
+            RepoAction::CherryPick(oid) => {
+                format!("Apply commit {} on top of HEAD?", &oid.to_string()[..7])
+            }
+            RepoAction::Revert(oid) => format!(
+                "Create a commit reverting commit {} on top of HEAD?",
+                &oid.to_string()[..7]
+            ),
+            _ => String::new(),
+        }
+    }
+
+    pub fn character_entered(&mut self, c: char) {
+        if let Some(input) = &mut self.input {
+            input.push(c);
+        }
+    }
+
+    pub fn on_backspace(&mut self) {
+        if let Some(input) = &mut self.input {
+            input.pop();
+        }
+    }
+}
@@ -0,0 +1,772 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+use std::path::Path;
+
+/// Actions that can be bound to keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    ShowHelp,
+    Quit,
+    OpenRepository,
+    SelectModel,
+    Reload,
+    ExportGraph,
+    PreviousPanel,
+    NextPanel,
+    Fullscreen,
+    DefaultView,
+    ToggleLayout,
+    ToggleBranches,
+    ToggleReflog,
+    Up,
+    Down,
+    UpFast,
+    DownFast,
+    Home,
+    End,
+    SecondaryUp,
+    SecondaryDown,
+    SecondaryUpFast,
+    SecondaryDownFast,
+    ClearSecondary,
+    ScrollLeft,
+    ScrollRight,
+    ScrollLeftFast,
+    ScrollRightFast,
+    Confirm,
+    SecondaryConfirm,
+    CompareTo,
+    Checkout,
+    CreateBranch,
+    CreateTag,
+    CreateAnnotatedTag,
+    DeleteBranch,
+    ApplyStash,
+    PopStash,
+    CherryPick,
+    Revert,
+    Yank,
+    YankShort,
+    OpenEditor,
+    DiffTool,
+    Pager,
+    OpenSearch,
+    SearchNext,
+    Filter,
+    FileHistory,
+    MoreContext,
+    LessContext,
+    ShowDiff,
+    ShowNew,
+    ShowOld,
+    ShowSplit,
+    ToggleBlame,
+    ToggleLineNumbers,
+    ToggleLineWrap,
+    ToggleWordDiff,
+    ToggleSyntaxHighlight,
+    ToggleFileTree,
+    ToggleDirectory,
+}
+
+/// Actions grouped by sections of the help screen and keymap file
+pub const SECTIONS: &[(&str, &[Action])] = &[
+    (
+        "General",
+        &[
+            Action::ShowHelp,
+            Action::Quit,
+            Action::OpenRepository,
+            Action::SelectModel,
+            Action::Reload,
+            Action::ExportGraph,
+        ],
+    ),
+    (
+        "Layout/panels",
+        &[
+            Action::PreviousPanel,
+            Action::NextPanel,
+            Action::Fullscreen,
+            Action::DefaultView,
+            Action::ToggleLayout,
+            Action::ToggleBranches,
+            Action::ToggleReflog,
+        ],
+    ),
+    (
+        "Navigate/select",
+        &[
+            Action::Up,
+            Action::Down,
+            Action::UpFast,
+            Action::DownFast,
+            Action::Home,
+            Action::End,
+            Action::SecondaryUp,
+            Action::SecondaryDown,
+            Action::SecondaryUpFast,
+            Action::SecondaryDownFast,
+            Action::ClearSecondary,
+            Action::ScrollLeft,
+            Action::ScrollRight,
+            Action::ScrollLeftFast,
+            Action::ScrollRightFast,
+            Action::Confirm,
+            Action::SecondaryConfirm,
+            Action::CompareTo,
+        ],
+    ),
+    (
+        "Repository actions",
+        &[
+            Action::Checkout,
+            Action::CreateBranch,
+            Action::CreateTag,
+            Action::CreateAnnotatedTag,
+            Action::DeleteBranch,
+            Action::ApplyStash,
+            Action::PopStash,
+            Action::CherryPick,
+            Action::Revert,
+        ],
+    ),
+    ("Clipboard", &[Action::Yank, Action::YankShort]),
+    (
+        "External programs",
+        &[Action::OpenEditor, Action::DiffTool, Action::Pager],
+    ),
+    (
+        "Search",
+        &[
+            Action::OpenSearch,
+            Action::SearchNext,
+            Action::Filter,
+            Action::FileHistory,
+        ],
+    ),
+    (
+        "Diffs panel",
+        &[
+            Action::MoreContext,
+            Action::LessContext,
+            Action::ShowDiff,
+            Action::ShowNew,
+            Action::ShowOld,
+            Action::ShowSplit,
+            Action::ToggleBlame,
+            Action::ToggleLineNumbers,
+            Action::ToggleLineWrap,
+            Action::ToggleWordDiff,
+            Action::ToggleSyntaxHighlight,
+        ],
+    ),
+    (
+        "Files panel",
+        &[Action::ToggleFileTree, Action::ToggleDirectory],
+    ),
+];
+
+impl Action {
+    /// Name of the action in the keymap file
+    pub fn name(&self) -> &'static str {
+        self.info().0
+    }
+
+    pub fn description(&self) -> &'static str {
+        self.info().1
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        self.info().2
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        SECTIONS
+            .iter()
+            .flat_map(|(_, actions)| actions.iter())
+            .find(|action| action.name() == name)
+            .cloned()
+    }
+
+    fn info(&self) -> (&'static str, &'static str, &'static [&'static str]) {
+        match self {
+            Action::ShowHelp => ("show_help", "Show this help", &["F1", "h"]),
+            Action::Quit => ("quit", "Quit", &["q"]),
+            Action::OpenRepository => ("open_repository", "Open repository", &["Ctrl+o"]),
+            Action::SelectModel => ("select_model", "Set branching model", &["m"]),
+            Action::Reload => ("reload", "Reload the repository", &["r"]),
+            Action::ExportGraph => (
+                "export_graph",
+                "Export the graph to an SVG or HTML file",
+                &["x"],
+            ),
+            Action::PreviousPanel => ("previous_panel", "Change to panel on the left", &["Left"]),
+            Action::NextPanel => ("next_panel", "Change to panel on the right", &["Right"]),
+            Action::Fullscreen => ("fullscreen", "Panel to fullscreen", &["Tab"]),
+            Action::DefaultView => ("default_view", "Return to default view", &["Esc"]),
+            Action::ToggleLayout => ("toggle_layout", "Toggle horizontal/vertical layout", &["l"]),
+            Action::ToggleBranches => ("toggle_branches", "Toggle show branch list", &["b"]),
+            Action::ToggleReflog => (
+                "toggle_reflog",
+                "Toggle reflog of HEAD (graph) or selected local branch (branch list)",
+                &["R"],
+            ),
+            Action::Up => ("up", "Select / navigate / scroll up", &["Up"]),
+            Action::Down => ("down", "Select / navigate / scroll down", &["Down"]),
+            Action::UpFast => ("up_fast", "Navigate up fast", &["Shift+Up"]),
+            Action::DownFast => ("down_fast", "Navigate down fast", &["Shift+Down"]),
+            Action::Home => ("home", "Navigate to HEAD/first", &["Home"]),
+            Action::End => ("end", "Navigate to last", &["End"]),
+            Action::SecondaryUp => (
+                "secondary_up",
+                "Secondary selection up (compare arbitrary commits)",
+                &["Ctrl+Up"],
+            ),
+            Action::SecondaryDown => (
+                "secondary_down",
+                "Secondary selection down (compare arbitrary commits)",
+                &["Ctrl+Down"],
+            ),
+            Action::SecondaryUpFast => (
+                "secondary_up_fast",
+                "Secondary selection up fast",
+                &["Ctrl+Shift+Up"],
+            ),
+            Action::SecondaryDownFast => (
+                "secondary_down_fast",
+                "Secondary selection down fast",
+                &["Ctrl+Shift+Down"],
+            ),
+            Action::ClearSecondary => (
+                "clear_secondary",
+                "Clear secondary selection",
+                &["Backspace"],
+            ),
+            Action::ScrollLeft => ("scroll_left", "Scroll left", &["Ctrl+Left"]),
+            Action::ScrollRight => ("scroll_right", "Scroll right", &["Ctrl+Right"]),
+            Action::ScrollLeftFast => {
+                ("scroll_left_fast", "Scroll left fast", &["Ctrl+Shift+Left"])
+            }
+            Action::ScrollRightFast => (
+                "scroll_right_fast",
+                "Scroll right fast",
+                &["Ctrl+Shift+Right"],
+            ),
+            Action::Confirm => (
+                "confirm",
+                "Jump to selected branch/tag, or to the commit of a blame line",
+                &["Enter"],
+            ),
+            Action::SecondaryConfirm => (
+                "secondary_confirm",
+                "Secondary selection to selected branch/tag",
+                &["Ctrl+Enter"],
+            ),
+            Action::CompareTo => (
+                "compare_to",
+                "Compare selected commit to a branch, tag or commit (clear with Backspace)",
+                &["C"],
+            ),
+            Action::Checkout => (
+                "checkout",
+                "Checkout selected commit (graph) or branch (branch list)",
+                &["c"],
+            ),
+            Action::CreateBranch => ("create_branch", "Create branch at selected commit", &["B"]),
+            Action::CreateTag => ("create_tag", "Create tag at selected commit", &["t"]),
+            Action::CreateAnnotatedTag => (
+                "create_annotated_tag",
+                "Create annotated tag at selected commit",
+                &["T"],
+            ),
+            Action::DeleteBranch => (
+                "delete_branch",
+                "Delete selected local branch or drop selected stash (branch list)",
+                &["Delete"],
+            ),
+            Action::ApplyStash => ("apply_stash", "Apply selected stash (branch list)", &["A"]),
+            Action::PopStash => (
+                "pop_stash",
+                "Apply and drop selected stash (branch list)",
+                &["O"],
+            ),
+            Action::CherryPick => (
+                "cherry_pick",
+                "Cherry-pick selected commit onto HEAD",
+                &["K"],
+            ),
+            Action::Revert => ("revert", "Revert selected commit", &["V"]),
+            Action::Yank => (
+                "yank",
+                "Copy hash (graph), message (commit), path (files) or hunk (diff)",
+                &["y"],
+            ),
+            Action::YankShort => ("yank_short", "Copy short hash of selected commit", &["Y"]),
+            Action::OpenEditor => (
+                "open_editor",
+                "Open selected file in $EDITOR, at the line in the diff panel",
+                &["e"],
+            ),
+            Action::DiffTool => (
+                "diff_tool",
+                "Compare versions of selected file in git difftool",
+                &["D"],
+            ),
+            Action::Pager => ("pager", "Show the commit's patch in $PAGER", &["P"]),
+            Action::OpenSearch => (
+                "open_search",
+                "Open search dialog (graph, files and diff panels)",
+                &["Ctrl+f"],
+            ),
+            Action::SearchNext => (
+                "search_next",
+                "Continue search, or open search dialog",
+                &["F3"],
+            ),
+            Action::Filter => (
+                "filter",
+                "Filter commits by author, date and message (dims other commits), or by path and range",
+                &["f"],
+            ),
+            Action::FileHistory => (
+                "file_history",
+                "Show history of the selected file (files panel, again to reset)",
+                &["p"],
+            ),
+            Action::MoreContext => (
+                "more_context",
+                "Increase number of diff context lines",
+                &["+"],
+            ),
+            Action::LessContext => (
+                "less_context",
+                "Decrease number of diff context lines",
+                &["-"],
+            ),
+            Action::ShowDiff => ("show_diff", "Show diff", &["d"]),
+            Action::ShowNew => ("show_new", "Show new version of file", &["n"]),
+            Action::ShowOld => ("show_old", "Show old version of file", &["o"]),
+            Action::ShowSplit => ("show_split", "Show side-by-side diff", &["v"]),
+            Action::ToggleBlame => (
+                "toggle_blame",
+                "Toggle blame (new/old file only), confirm jumps to the line's commit",
+                &["a"],
+            ),
+            Action::ToggleLineNumbers => {
+                ("toggle_line_numbers", "Toggle line numbers", &["Ctrl+l"])
+            }
+            Action::ToggleLineWrap => (
+                "toggle_line_wrap",
+                "Toggle line wrapping (not in split mode)",
+                &["Ctrl+w"],
+            ),
+            Action::ToggleWordDiff => (
+                "toggle_word_diff",
+                "Toggle word-level highlighting of changes",
+                &["Ctrl+e"],
+            ),
+            Action::ToggleSyntaxHighlight => (
+                "toggle_syntax_highlight",
+                "Toggle syntax highlighting",
+                &["s"],
+            ),
+            Action::ToggleFileTree => (
+                "toggle_file_tree",
+                "Toggle grouping files by directory",
+                &["Ctrl+t"],
+            ),
+            Action::ToggleDirectory => (
+                "toggle_directory",
+                "Expand/collapse directory (file tree only)",
+                &["Space"],
+            ),
+        }
+    }
+}
+
+/// A key with modifiers. For characters, the case replaces the Shift modifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn from_event(event: &KeyEvent) -> Self {
+        let modifiers =
+            event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        match event.code {
+            KeyCode::Char(c) => {
+                let c = if modifiers.contains(KeyModifiers::SHIFT) {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                };
+                KeyBinding {
+                    code: KeyCode::Char(c),
+                    modifiers: modifiers - KeyModifiers::SHIFT,
+                }
+            }
+            code => KeyBinding { code, modifiers },
+        }
+    }
+
+    /// Parses keys like `q`, `B`, `Ctrl+f`, `Shift+Up`, `F3` or `Enter`
+    pub fn parse(key: &str) -> Result<Self, String> {
+        let err = || format!("Invalid key '{}'", key);
+        let mut parts: Vec<&str> = key.split('+').collect();
+        let name = if parts.len() > 1 && parts[parts.len() - 2..].iter().all(|p| p.is_empty()) {
+            // The key '+' itself
+            parts.truncate(parts.len() - 2);
+            "+"
+        } else {
+            parts.pop().ok_or_else(err)?
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return Err(err()),
+            };
+        }
+
+        let code = match name.to_lowercase().as_str() {
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "space" => KeyCode::Char(' '),
+            lower => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => match lower.strip_prefix('f').map(|num| num.parse::<u8>()) {
+                        Some(Ok(num)) if (1..=12).contains(&num) => KeyCode::F(num),
+                        _ => return Err(err()),
+                    },
+                }
+            }
+        };
+
+        Ok(KeyBinding::from_event(&KeyEvent::new(code, modifiers)))
+    }
+
+    /// Name of the key for the help screen, like `Shift + B` or `Ctrl + F`
+    pub fn help_name(&self) -> String {
+        let mut parts = vec![];
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            parts.push("Ctrl".to_string());
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            parts.push("Alt".to_string());
+        }
+        match self.code {
+            KeyCode::Char(c) if c.is_uppercase() => {
+                parts.push("Shift".to_string());
+                parts.push(c.to_string());
+            }
+            KeyCode::Char(c) => parts.push(c.to_uppercase().to_string()),
+            _ => {
+                if self.modifiers.contains(KeyModifiers::SHIFT) {
+                    parts.push("Shift".to_string());
+                }
+                parts.push(key_name(self.code));
+            }
+        }
+        parts.join(" + ")
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(c) => write!(f, "{}", c),
+            code => write!(f, "{}", key_name(code)),
+        }
+    }
+}
+
+fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(num) => format!("F{}", num),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Key bindings of all actions
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<(Action, Vec<KeyBinding>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = SECTIONS
+            .iter()
+            .flat_map(|(_, actions)| actions.iter())
+            .map(|action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .map(|key| KeyBinding::parse(key).unwrap())
+                    .collect();
+                (*action, keys)
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    /// Loads the keymap from a TOML file, or creates the file with the default keymap if it does
+    /// not exist. Actions not in the file keep their default keys, except keys used in the file.
+    pub fn load_or_create(path: &Path) -> Result<Self, String> {
+        let mut keymap = Keymap::default();
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+            }
+            std::fs::write(path, keymap.to_toml()).map_err(|err| err.to_string())?;
+            return Ok(keymap);
+        }
+
+        let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        keymap
+            .apply_toml(&content)
+            .map_err(|err| format!("Error in keymap file {}\n{}", path.display(), err))?;
+        Ok(keymap)
+    }
+
+    fn apply_toml(&mut self, content: &str) -> Result<(), String> {
+        let table = content
+            .parse::<toml::Table>()
+            .map_err(|err| err.to_string())?;
+
+        let mut user_bindings: Vec<(Action, Vec<KeyBinding>)> = vec![];
+        for (name, value) in table {
+            let action =
+                Action::from_name(&name).ok_or_else(|| format!("Unknown action '{}'", name))?;
+            let keys = match value {
+                toml::Value::String(key) => vec![key],
+                toml::Value::Array(keys) => keys
+                    .into_iter()
+                    .map(|key| match key {
+                        toml::Value::String(key) => Ok(key),
+                        other => Err(format!(
+                            "Expected a key string for '{}', got {}",
+                            name, other
+                        )),
+                    })
+                    .collect::<Result<_, _>>()?,
+                other => {
+                    return Err(format!(
+                        "Expected a key or a list of keys for '{}', got {}",
+                        name, other
+                    ))
+                }
+            };
+            let keys = keys
+                .iter()
+                .map(|key| KeyBinding::parse(key))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for key in &keys {
+                if let Some((other, _)) = user_bindings
+                    .iter()
+                    .find(|(_, other_keys)| other_keys.contains(key))
+                {
+                    return Err(format!(
+                        "Key '{}' is bound to '{}' and '{}'",
+                        key,
+                        other.name(),
+                        name
+                    ));
+                }
+            }
+            user_bindings.push((action, keys));
+        }
+
+        for (action, keys) in &mut self.bindings {
+            if let Some((_, user_keys)) = user_bindings.iter().find(|(a, _)| a == action) {
+                *keys = user_keys.clone();
+            } else {
+                keys.retain(|key| !user_bindings.iter().any(|(_, user)| user.contains(key)));
+            }
+        }
+        Ok(())
+    }
+
+    fn to_toml(&self) -> String {
+        let mut out = "# Key bindings of git-igitt, with the defaults commented out.\n\
+                       # Keys are given like \"q\", \"B\" (i.e. Shift + b), \"Ctrl+f\", \"Shift+Up\", \"F3\" or \"Enter\".\n\
+                       # Keys bound here are removed from actions not listed in this file.\n"
+            .to_string();
+        for (section, actions) in SECTIONS {
+            out.push_str(&format!("\n# {}\n", section));
+            for action in actions.iter() {
+                let keys = self
+                    .keys(*action)
+                    .iter()
+                    .map(|key| toml::Value::String(key.to_string()).to_string())
+                    .collect::<Vec<_>>();
+                out.push_str(&format!("# {} = [{}]\n", action.name(), keys.join(", ")));
+            }
+        }
+        out
+    }
+
+    /// The action bound to the key of an event
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        let key = KeyBinding::from_event(event);
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyBinding] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| &keys[..])
+            .unwrap_or(&[])
+    }
+
+    /// Help name of the first key bound to an action, for hints. Empty if it is unbound.
+    pub fn key_name(&self, action: Action) -> String {
+        self.keys(action)
+            .first()
+            .map(|key| key.help_name())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        KeyBinding { code, modifiers }
+    }
+
+    #[test]
+    fn parse_keys() {
+        let parse = |k| KeyBinding::parse(k).unwrap();
+        assert_eq!(parse("q"), key(KeyCode::Char('q'), KeyModifiers::NONE));
+        assert_eq!(parse("B"), key(KeyCode::Char('B'), KeyModifiers::NONE));
+        assert_eq!(parse("Shift+b"), parse("B"));
+        assert_eq!(
+            parse("Ctrl+f"),
+            key(KeyCode::Char('f'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(parse("ctrl+alt+x"), parse("Alt+Ctrl+x"));
+        assert_eq!(parse("Shift+Up"), key(KeyCode::Up, KeyModifiers::SHIFT));
+        assert_eq!(parse("F3"), key(KeyCode::F(3), KeyModifiers::NONE));
+        assert_eq!(parse("f"), key(KeyCode::Char('f'), KeyModifiers::NONE));
+        assert_eq!(
+            parse("PageDown"),
+            key(KeyCode::PageDown, KeyModifiers::NONE)
+        );
+        assert_eq!(parse("Space"), key(KeyCode::Char(' '), KeyModifiers::NONE));
+        assert_eq!(parse("+"), key(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(
+            parse("Ctrl++"),
+            key(KeyCode::Char('+'), KeyModifiers::CONTROL)
+        );
+    }
+
+    #[test]
+    fn parse_invalid_keys() {
+        for invalid in ["", "Foo", "F13", "F0", "Meta+x", "Ctrl+", "ab"] {
+            assert!(KeyBinding::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for k in [
+            "q",
+            "B",
+            "Ctrl+f",
+            "Alt+Shift+Left",
+            "F12",
+            "Space",
+            "+",
+            "Ctrl++",
+        ] {
+            let binding = KeyBinding::parse(k).unwrap();
+            assert_eq!(KeyBinding::parse(&binding.to_string()).unwrap(), binding);
+        }
+        assert_eq!(
+            KeyBinding::parse("Shift+x").unwrap().help_name(),
+            "Shift + X"
+        );
+        assert_eq!(KeyBinding::parse("ctrl+f").unwrap().help_name(), "Ctrl + F");
+    }
+
+    #[test]
+    fn default_keys_are_unique() {
+        let keymap = Keymap::default();
+        let mut seen = std::collections::HashSet::new();
+        for (action, keys) in &keymap.bindings {
+            for key in keys {
+                assert!(seen.insert(*key), "{} bound twice, at {:?}", key, action);
+            }
+        }
+    }
+
+    #[test]
+    fn user_keys_replace_defaults() {
+        let mut keymap = Keymap::default();
+        keymap
+            .apply_toml("reload = \"q\"\nquit = [\"x\", \"Ctrl+c\"]")
+            .unwrap();
+        let q = KeyBinding::parse("q").unwrap();
+        assert_eq!(keymap.keys(Action::Reload), &[q]);
+        assert_eq!(keymap.keys(Action::Quit).len(), 2);
+        assert_eq!(keymap.key_name(Action::Quit), "X");
+
+        // Keys used in the file are removed from actions not in the file
+        let mut keymap = Keymap::default();
+        keymap.apply_toml("reload = \"h\"").unwrap();
+        assert_eq!(
+            keymap.keys(Action::ShowHelp),
+            &[KeyBinding::parse("F1").unwrap()]
+        );
+
+        assert!(Keymap::default()
+            .apply_toml("no_such_action = \"q\"")
+            .is_err());
+        assert!(Keymap::default().apply_toml("quit = 1").is_err());
+        assert!(Keymap::default()
+            .apply_toml("quit = \"z\"\nreload = \"z\"")
+            .is_err());
+    }
+}
@@ -1,6 +1,11 @@
 pub mod app;
+pub mod config;
 pub mod dialogs;
+pub mod keymap;
+pub mod loader;
 pub mod settings;
+pub mod theme;
 pub mod ui;
 pub mod util;
+pub mod watcher;
 pub mod widgets;
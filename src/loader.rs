@@ -0,0 +1,360 @@
+use crate::util::filter::{CommitFilter, PathHistory, RevisionRange};
+use git2::{Oid, Repository};
+use git_graph::graph::{CommitInfo, GitGraph};
+use git_graph::print::format::CommitFormat;
+use git_graph::print::unicode::print_unicode;
+use git_graph::settings::{BranchOrder, BranchSettings, Characters, MergePatterns, Settings};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+/// Number of commits loaded in the first step of incremental loading
+const INITIAL_LOAD_COMMITS: usize = 1000;
+/// Factor by which the number of commits grows in each step of incremental loading
+const LOAD_GROWTH_FACTOR: usize = 10;
+
+/// A graph with its printed lines, as produced by `print_unicode`
+pub struct LoadedGraph {
+    pub graph: GitGraph,
+    pub graph_lines: Vec<String>,
+    pub text_lines: Vec<String>,
+    pub indices: Vec<usize>,
+    /// Number of loaded commits, before restricting the graph to the filter's range and paths
+    pub total_commits: usize,
+    /// The commits touching the filter's paths
+    pub path_history: PathHistory,
+    /// Error resolving the filter's range. The graph is not restricted to the range then.
+    pub range_error: Option<String>,
+    /// Whether all commits are loaded, or only the first ones
+    pub complete: bool,
+}
+
+/// Builds the graph of a repository in a background thread.
+///
+/// When loading incrementally, the graph is built for the first `INITIAL_LOAD_COMMITS` commits,
+/// then for ten times as many, and so on until all commits are loaded.
+/// Each step is sent to the UI as soon as it is finished.
+/// The thread stops when the loader is dropped.
+///
+/// With a revision range or paths in the filter, the graph shows only the commits in the range
+/// and touching the paths.
+pub struct GraphLoader {
+    receiver: Receiver<Result<LoadedGraph, String>>,
+    pub started: Instant,
+    pub incremental: bool,
+}
+
+impl GraphLoader {
+    pub fn start(
+        path: PathBuf,
+        settings: &Settings,
+        max_commits: Option<usize>,
+        incremental: bool,
+        filter: &CommitFilter,
+    ) -> Self {
+        let (sender, receiver) = channel();
+        let settings = copy_settings(settings);
+        let range = filter.range.clone();
+        let mut path_history = PathHistory::new(filter.paths.clone());
+
+        thread::spawn(move || {
+            let mut limit = if incremental {
+                Some(INITIAL_LOAD_COMMITS)
+            } else {
+                None
+            };
+            loop {
+                let step_limit = match (limit, max_commits) {
+                    (Some(limit), Some(max)) => Some(limit.min(max)),
+                    (limit, max) => limit.or(max),
+                };
+                let loaded = load_graph(
+                    &path,
+                    &settings,
+                    step_limit,
+                    range.as_ref(),
+                    &mut path_history,
+                );
+                let loaded = loaded.map(|mut loaded| {
+                    loaded.complete = step_limit
+                        .map(|step_limit| {
+                            loaded.total_commits < step_limit || Some(step_limit) == max_commits
+                        })
+                        .unwrap_or(true);
+                    loaded
+                });
+                let done = loaded.as_ref().map(|l| l.complete).unwrap_or(true);
+                if sender.send(loaded).is_err() || done {
+                    break;
+                }
+                limit = limit.map(|limit| limit * LOAD_GROWTH_FACTOR);
+            }
+        });
+
+        GraphLoader {
+            receiver,
+            started: Instant::now(),
+            incremental,
+        }
+    }
+
+    /// The most recent graph loaded since the last call, if any
+    pub fn poll(&mut self) -> Option<Result<LoadedGraph, String>> {
+        let mut latest = None;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(loaded) => {
+                    let is_err = loaded.is_err();
+                    latest = Some(loaded);
+                    if is_err {
+                        break;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if latest.is_none() {
+                        latest = Some(Err("Loading the graph failed unexpectedly".to_string()));
+                    }
+                    break;
+                }
+            }
+        }
+        latest
+    }
+}
+
+fn load_graph(
+    path: &Path,
+    settings: &Settings,
+    max_commits: Option<usize>,
+    range: Option<&RevisionRange>,
+    path_history: &mut PathHistory,
+) -> Result<LoadedGraph, String> {
+    let repository = Repository::open(path).map_err(|err| err.message().to_string())?;
+    let mut graph = GitGraph::new(repository, settings, max_commits)?;
+    let total_commits = graph.commits.len();
+    let mut range_error = None;
+    let range_commits = range.and_then(|range| match range.commits(&graph.repository) {
+        Ok(commits) => Some(commits),
+        Err(err) => {
+            range_error = Some(err);
+            None
+        }
+    });
+    let with_paths = !path_history.paths.is_empty();
+    if with_paths {
+        // Only commits added since the last step are processed
+        path_history.update(&graph.repository, graph.commits.iter().map(|info| info.oid))?;
+    }
+    if range_commits.is_some() || with_paths {
+        restrict_graph(&mut graph, |oid| {
+            range_commits
+                .as_ref()
+                .map(|commits| commits.contains(oid))
+                .unwrap_or(true)
+                && (!with_paths || path_history.commits.contains_key(oid))
+        });
+    }
+    let (graph_lines, text_lines, indices) = print_unicode(&graph, settings)?;
+    Ok(LoadedGraph {
+        graph,
+        graph_lines,
+        text_lines,
+        indices,
+        total_commits,
+        path_history: path_history.clone(),
+        range_error,
+        complete: false,
+    })
+}
+
+/// Removes the commits not to keep from the graph.
+///
+/// Like history simplification of `git log`, parents are replaced by their nearest kept
+/// first-parent ancestor, so that the lines of the graph stay connected.
+/// Branches are kept, so that columns and colors do not change.
+fn restrict_graph(graph: &mut GitGraph, keep: impl Fn(&Oid) -> bool) {
+    let commits = std::mem::take(&mut graph.commits);
+    // Parents come after their children, so ancestors are resolved first
+    let mut nearest: Vec<Option<Oid>> = vec![None; commits.len()];
+    for (idx, info) in commits.iter().enumerate().rev() {
+        nearest[idx] = if keep(&info.oid) {
+            Some(info.oid)
+        } else {
+            info.parents[0]
+                .and_then(|parent| graph.indices.get(&parent))
+                .and_then(|parent_idx| nearest[*parent_idx])
+        };
+    }
+    // Parents beyond the loaded commits are kept as they are
+    let rewrite = |parent: Option<Oid>| {
+        parent.and_then(|oid| match graph.indices.get(&oid) {
+            Some(idx) => nearest[*idx],
+            None => Some(oid),
+        })
+    };
+
+    let mut kept: Vec<CommitInfo> = vec![];
+    for (idx, mut info) in commits.into_iter().enumerate() {
+        if nearest[idx] == Some(info.oid) {
+            let first = rewrite(info.parents[0]);
+            let second = rewrite(info.parents[1]).filter(|oid| Some(*oid) != first);
+            info.parents = [first, second];
+            info.children.clear();
+            kept.push(info);
+        }
+    }
+
+    graph.indices = kept
+        .iter()
+        .enumerate()
+        .map(|(idx, info)| (info.oid, idx))
+        .collect();
+    for idx in 0..kept.len() {
+        let oid = kept[idx].oid;
+        for parent in kept[idx].parents.into_iter().flatten() {
+            if let Some(parent_idx) = graph.indices.get(&parent) {
+                kept[*parent_idx].children.push(oid);
+            }
+        }
+    }
+    graph.commits = kept;
+}
+
+/// Copies the settings for use in another thread, as `Settings` does not implement `Clone`
+pub fn copy_settings(settings: &Settings) -> Settings {
+    let branches = &settings.branches;
+    Settings {
+        reverse_commit_order: settings.reverse_commit_order,
+        debug: settings.debug,
+        compact: settings.compact,
+        colored: settings.colored,
+        include_remote: settings.include_remote,
+        format: match &settings.format {
+            CommitFormat::OneLine => CommitFormat::OneLine,
+            CommitFormat::Short => CommitFormat::Short,
+            CommitFormat::Medium => CommitFormat::Medium,
+            CommitFormat::Full => CommitFormat::Full,
+            CommitFormat::Format(format) => CommitFormat::Format(format.clone()),
+        },
+        wrapping: settings.wrapping,
+        characters: Characters {
+            chars: settings.characters.chars.clone(),
+        },
+        branch_order: match settings.branch_order {
+            BranchOrder::ShortestFirst(forward) => BranchOrder::ShortestFirst(forward),
+            BranchOrder::LongestFirst(forward) => BranchOrder::LongestFirst(forward),
+        },
+        branches: BranchSettings {
+            persistence: branches.persistence.clone(),
+            order: branches.order.clone(),
+            terminal_colors: branches.terminal_colors.clone(),
+            terminal_colors_unknown: branches.terminal_colors_unknown.clone(),
+            svg_colors: branches.svg_colors.clone(),
+            svg_colors_unknown: branches.svg_colors_unknown.clone(),
+        },
+        merge_patterns: MergePatterns {
+            patterns: settings.merge_patterns.patterns.clone(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::git::tests::{commit, init_repository, stage};
+    use git_graph::settings::BranchSettingsDef;
+
+    fn settings() -> Settings {
+        Settings {
+            reverse_commit_order: false,
+            debug: false,
+            colored: false,
+            compact: true,
+            include_remote: true,
+            format: CommitFormat::OneLine,
+            wrapping: None,
+            characters: Characters::thin(),
+            branch_order: BranchOrder::ShortestFirst(true),
+            branches: BranchSettings::from(BranchSettingsDef::none()).unwrap(),
+            merge_patterns: MergePatterns::default(),
+        }
+    }
+
+    /// A repository with a linear history of four commits, oldest first
+    fn linear_repository(name: &str) -> (PathBuf, Vec<Oid>) {
+        let (path, repository) = init_repository(name);
+        let mut oids: Vec<Oid> = vec![];
+        for file in ["a", "b", "c", "d"] {
+            stage(&repository, &path, file, file);
+            oids.push(commit(&repository, None, oids.last().cloned()));
+        }
+        (path, oids)
+    }
+
+    #[test]
+    fn restricted_graph_connects_nearest_ancestors() {
+        let (path, oids) = linear_repository("restrict");
+        let repository = Repository::open(&path).unwrap();
+        let mut graph = GitGraph::new(repository, &settings(), None).unwrap();
+        restrict_graph(&mut graph, |oid| *oid == oids[1] || *oid == oids[3]);
+
+        let commits: Vec<_> = graph
+            .commits
+            .iter()
+            .map(|info| (info.oid, info.parents, info.children.clone()))
+            .collect();
+        assert_eq!(
+            commits,
+            vec![
+                (oids[3], [Some(oids[1]), None], vec![]),
+                (oids[1], [None, None], vec![oids[3]]),
+            ]
+        );
+        assert_eq!(graph.indices.len(), 2);
+        assert_eq!(graph.indices.get(&oids[1]), Some(&1));
+        let (graph_lines, _, indices) = print_unicode(&graph, &settings()).unwrap();
+        assert_eq!(graph_lines.len(), 2);
+        assert_eq!(indices, vec![0, 1]);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn load_graph_in_range() {
+        let (path, oids) = linear_repository("load-range");
+        let range = RevisionRange::parse(&format!("{}..{}", oids[1], oids[3])).unwrap();
+        let mut history = PathHistory::default();
+        let loaded = load_graph(&path, &settings(), None, Some(&range), &mut history).unwrap();
+        assert_eq!(loaded.total_commits, 4);
+        assert_eq!(loaded.range_error, None);
+        let commits: Vec<_> = loaded.graph.commits.iter().map(|info| info.oid).collect();
+        assert_eq!(commits, vec![oids[3], oids[2]]);
+
+        let range = RevisionRange::parse("no-such-branch..HEAD").unwrap();
+        let loaded = load_graph(&path, &settings(), None, Some(&range), &mut history).unwrap();
+        assert!(loaded.range_error.is_some());
+        assert_eq!(loaded.graph.commits.len(), 4);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn load_graph_of_paths() {
+        let (path, oids) = linear_repository("load-paths");
+        let mut history = PathHistory::new(vec!["b".to_string(), "d".to_string()]);
+        let loaded = load_graph(&path, &settings(), Some(2), None, &mut history).unwrap();
+        let commits: Vec<_> = loaded.graph.commits.iter().map(|info| info.oid).collect();
+        assert_eq!(commits, vec![oids[3]]);
+
+        let loaded = load_graph(&path, &settings(), None, None, &mut history).unwrap();
+        assert_eq!(loaded.total_commits, 4);
+        let commits: Vec<_> = loaded.graph.commits.iter().map(|info| info.oid).collect();
+        assert_eq!(commits, vec![oids[3], oids[1]]);
+        assert_eq!(loaded.graph.commits[0].parents, [Some(oids[1]), None]);
+        assert_eq!(
+            loaded.path_history.commits.get(&oids[1]),
+            Some(&"b".to_string())
+        );
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -1,6 +1,9 @@
 use clap::{crate_version, Arg, Command};
 use crossterm::{
-    event::{self, Event as CEvent, KeyCode, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -16,18 +19,27 @@ use git_graph::{
     },
 };
 use git_igitt::app::DiffMode;
+use git_igitt::config::Config;
+use git_igitt::keymap::{Action, Keymap};
 use git_igitt::settings::AppSettings;
+use git_igitt::theme::Theme;
+use git_igitt::util::export::{export_graph, ExportFormat};
+use git_igitt::util::external::ExternalCommand;
+use git_igitt::util::filter::CommitFilter;
+use git_igitt::util::syntax_highlight;
+use git_igitt::watcher::{Changes, RepoWatcher};
 use git_igitt::{
-    app::{ActiveView, App, CurrentBranches},
+    app::{get_pseudo_commits, ActiveView, App, CurrentBranches},
     dialogs::FileDialog,
     ui,
 };
 use platform_dirs::AppDirs;
 use std::cell::Cell;
+use std::collections::HashSet;
 use std::time::Instant;
 use std::{
     error::Error,
-    io::stdout,
+    io::{stdout, ErrorKind, IsTerminal, Stdout, Write},
     path::{Path, PathBuf},
     str::FromStr,
     time::Duration,
@@ -35,18 +47,27 @@ use std::{
 use tui::{backend::CrosstermBackend, Terminal};
 
 const REPO_CONFIG_FILE: &str = "git-graph.toml";
+const KEYMAP_FILE: &str = "keymap.toml";
+const CONFIG_FILE: &str = "config.toml";
+/// Directory for additional syntax definitions and themes
+const SYNTAX_DIR: &str = "syntax";
+/// Directory for color theme files
+const THEMES_DIR: &str = "themes";
 const CHECK_CHANGE_RATE: u64 = 2000;
+const CHECK_LOADING_RATE: u64 = 100;
+const CHECK_WATCHER_RATE: u64 = 250;
 const INITIAL_KEY_REPEAT_TIME: u128 = 100;
 const MIN_KEY_REPEAT_TIME: u128 = 50;
 
 enum Event<I> {
     Input(I),
+    Mouse(MouseEvent),
     Update,
 }
 
 fn reset_terminal() -> std::result::Result<(), Box<dyn std::error::Error>> {
     disable_raw_mode()?;
-    crossterm::execute!(std::io::stdout(), LeaveAlternateScreen)?;
+    crossterm::execute!(std::io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
 
     Ok(())
 }
@@ -66,9 +87,12 @@ fn main() {
         Ok(_) => 0,
         Err(err) => {
             let mut sout = stdout();
-            match execute!(sout, LeaveAlternateScreen) {
-                Ok(_) => {}
-                Err(err) => eprintln!("{}", err),
+            // Keep piped output, e.g. of --print, free of control sequences
+            if sout.is_terminal() {
+                match execute!(sout, LeaveAlternateScreen, DisableMouseCapture) {
+                    Ok(_) => {}
+                    Err(err) => eprintln!("{}", err),
+                }
             }
             eprintln!("{}", err);
             1
@@ -117,6 +141,11 @@ fn setup_logger(log_level: &str) {
 
 fn from_args() -> Result<(), String> {
     let app_dir = AppDirs::new(Some("git-graph"), false).unwrap().config_dir;
+    let keymap = Keymap::load_or_create(&app_dir.join(KEYMAP_FILE))?;
+    let config_path = app_dir.join(CONFIG_FILE);
+    let mut config = Config::load(&config_path)?;
+    let syntax_dir = app_dir.join(SYNTAX_DIR);
+    let themes_dir = app_dir.join(THEMES_DIR);
     let mut models_dir = app_dir;
     models_dir.push("models");
 
@@ -132,9 +161,14 @@ fn from_args() -> Result<(), String> {
                  git-graph                   -> Start application\n    \
                  git-graph --style round     -> Start application with a different graph style\n    \
                  git-graph --model <model>   -> Start application using a certain <model>\n    \
+                 git-igitt -- <path>         -> Start application with the history of <path>\n    \
+                 git-igitt main..feature     -> Start application comparing main and feature\n    \
                  git-graph model --list      -> List available branching models\n    \
                  git-graph model             -> Show repo's current branching models\n    \
-                 git-graph model <model>     -> Permanently set model <model> for this repo",
+                 git-graph model <model>     -> Permanently set model <model> for this repo\n    \
+                 git-igitt config            -> Print the effective configuration\n    \
+                 git-igitt theme light       -> Print the light theme, as a template for themes\n    \
+                 git-igitt --print -n 20     -> Print the graph of the last 20 commits, without UI",
         )
         .arg(
             Arg::new("path")
@@ -153,6 +187,55 @@ fn from_args() -> Result<(), String> {
                 .num_args(1)
                 .value_name("n"),
         )
+        .arg(
+            Arg::new("author")
+                .long("author")
+                .help("Show only commits by authors matching the given name or email.\n\
+                       Other commits are dimmed.")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .help("Show only commits more recent than the given date (YYYY-MM-DD).")
+                .required(false)
+                .num_args(1)
+                .value_name("date"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .help("Show only commits older than the given date (YYYY-MM-DD).")
+                .required(false)
+                .num_args(1)
+                .value_name("date"),
+        )
+        .arg(
+            Arg::new("grep")
+                .long("grep")
+                .help("Show only commits with a message containing the given text.")
+                .required(false)
+                .num_args(1)
+                .value_name("text"),
+        )
+        .arg(
+            Arg::new("range")
+                .help("Show only commits in the revision range A..B or A...B,\n\
+                       and compare B to A, or to the merge base of A and B for A...B.")
+                .required(false)
+                .num_args(1)
+                .value_name("range"),
+        )
+        .arg(
+            Arg::new("paths")
+                .help("Show only commits touching the given files or directories.\n\
+                       A single file is followed through renames.")
+                .required(false)
+                .num_args(1..)
+                .last(true)
+                .value_name("path"),
+        )
         .arg(
             Arg::new("model")
                 .long("model")
@@ -172,6 +255,14 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(0),
         )
+        .arg(
+            Arg::new("remotes")
+                .long("remotes")
+                .help("Show remote branches, even if disabled by include_remote in config.toml.")
+                .required(false)
+                .num_args(0)
+                .conflicts_with("local"),
+        )
         .arg(
             Arg::new("reverse")
                 .long("reverse")
@@ -189,6 +280,14 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(0),
         )
+        .arg(
+            Arg::new("print")
+                .long("print")
+                .help("Print the graph to stdout and exit, without the interactive UI.\n\
+                       Colors are disabled if stdout is not a terminal, unless --color always.")
+                .required(false)
+                .num_args(0),
+        )
         .arg(
             Arg::new("log-level")
                 .long("log-level")
@@ -290,7 +389,37 @@ fn from_args() -> Result<(), String> {
                     .help("List all available branching models.")
                     .required(false)
                     .num_args(0),
-            ));
+            ))
+        .subcommand(Command::new("config")
+            .about("Prints the effective configuration, i.e. the user's config file merged\n\
+                    with the defaults and the given options."))
+        .subcommand(Command::new("theme")
+            .about("Prints the color theme, as a template for theme files.")
+            .arg(
+                Arg::new("name")
+                    .help("Theme to print. One of [dark|light|high-contrast], or a theme file's name.\n\
+                           Default: the theme from the configuration.")
+                    .required(false)
+                    .index(1)))
+        .subcommand(Command::new("export")
+            .about("Exports the graph to a standalone SVG file or HTML page.\n\
+                    Respects options like --max-count, --local and --model.")
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .help("Export format. One of [svg|html].\n\
+                           Default: by the extension of the output file, or svg.")
+                    .value_name("format")
+                    .num_args(1)
+                    .required(false))
+            .arg(
+                Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .help("File to write to. Prints to stdout if not given.")
+                    .value_name("file")
+                    .num_args(1)
+                    .required(false)));
 
     let matches = app.get_matches();
 
@@ -353,7 +482,58 @@ fn from_args() -> Result<(), String> {
         },
     };
 
-    let include_remote = !matches.get_flag("local");
+    let filter = CommitFilter::from_args(
+        matches.get_one::<String>("author").map(|x| &**x),
+        matches.get_one::<String>("since").map(|x| &**x),
+        matches.get_one::<String>("until").map(|x| &**x),
+        matches.get_one::<String>("grep").map(|x| &**x),
+        matches
+            .get_many::<String>("paths")
+            .map(|paths| paths.cloned().collect())
+            .unwrap_or_default(),
+        matches.get_one::<String>("range").map(|x| &**x),
+    )?;
+    if let (Some(range), Ok(repository)) = (&filter.range, &repository) {
+        range.endpoints(repository)?;
+    }
+
+    if let Some(tab_width) = tab_width {
+        config.tab_width = tab_width;
+    }
+    if matches.get_flag("local") {
+        config.include_remote = false;
+    }
+    if matches.get_flag("remotes") {
+        config.include_remote = true;
+    }
+    if let Some(style) = matches.get_one::<String>("style") {
+        config.style = style.clone();
+    }
+    if let Some(format) = matches.get_one::<String>("format") {
+        config.format = format.clone();
+    }
+    if matches.get_flag("no-color") {
+        config.color = "never".to_string();
+    } else if let Some(color) = matches.get_one::<String>("color") {
+        config.color = color.clone();
+    }
+
+    if matches.subcommand_matches("config").is_some() {
+        println!("# {}\n\n{}", config_path.display(), config.to_toml()?);
+        return Ok(());
+    }
+
+    let theme_name = matches
+        .subcommand_matches("theme")
+        .and_then(|matches| matches.get_one::<String>("name"))
+        .unwrap_or(&config.theme);
+    let theme = Theme::load(theme_name, &themes_dir)?;
+    if matches.subcommand_matches("theme").is_some() {
+        print!("{}", theme.to_toml()?);
+        return Ok(());
+    }
+
+    let include_remote = config.include_remote;
     let reverse_commit_order = matches.get_flag("reverse");
 
     let compact = !matches.get_flag("sparse");
@@ -361,50 +541,41 @@ fn from_args() -> Result<(), String> {
         setup_logger(log_level)
     }
 
-    let style = matches
-        .get_one::<String>("style")
-        .map(|s| Characters::from_str(s))
-        .unwrap_or_else(|| Ok(Characters::round()))?;
+    let style = Characters::from_str(&config.style)?;
 
     let model = matches.get_one::<String>("model");
 
-    let format = match matches.get_one::<String>("format") {
-        None => CommitFormat::OneLine,
-        Some(str) => CommitFormat::from_str(str)?,
-    };
+    let format = CommitFormat::from_str(&config.format)?;
 
-    let colored = if matches.get_flag("no-color") {
-        false
-    } else if let Some(mode) = matches.get_one::<String>("color") {
-        match mode.as_str() {
-            "auto" => {
-                !cfg!(windows) || {
-                    yansi::enable();
-                    yansi::is_enabled()
-                }
-            }
-            "always" => {
-                if cfg!(windows) {
-                    yansi::enable();
-                }
-                true
+    let print = matches.get_flag("print");
+
+    let colored = match config.color.as_str() {
+        "auto" if print && !stdout().is_terminal() => false,
+        "auto" => {
+            !cfg!(windows) || {
+                yansi::enable();
+                yansi::is_enabled()
             }
-            "never" => false,
-            other => {
-                return Err(format!(
-                    "Unknown color mode '{}'. Supports [auto|always|never].",
-                    other
-                ))
+        }
+        "always" => {
+            if cfg!(windows) {
+                yansi::enable();
             }
+            true
         }
-    } else {
-        !cfg!(windows) || {
-            yansi::enable();
-            yansi::is_enabled()
+        "never" => false,
+        other => {
+            return Err(format!(
+                "Unknown color mode '{}'. Supports [auto|always|never].",
+                other
+            ))
         }
     };
 
-    let app_settings = AppSettings::default().tab_width(tab_width.unwrap_or(4));
+    let app_settings = AppSettings::default()
+        .config(&config)
+        .keymap(keymap)
+        .theme(theme);
 
     let settings = Settings {
         reverse_commit_order,
@@ -420,12 +591,42 @@ fn from_args() -> Result<(), String> {
         merge_patterns: MergePatterns::default(),
     };
 
+    if print {
+        return match repository {
+            Ok(repository) => print_graph(repository, settings, model.map(|x| &**x), commit_limit),
+            Err(err) => Err(format!("ERROR: {}\n       Navigate into a repository before running git-igitt, or use option --path", err.message())),
+        };
+    }
+
+    if let Some(matches) = matches.subcommand_matches("export") {
+        let output = matches.get_one::<String>("output").map(PathBuf::from);
+        let export_format = match (matches.get_one::<String>("format"), &output) {
+            (Some(format), _) => ExportFormat::from_str(format)?,
+            (None, Some(output)) => ExportFormat::from_path(output)?,
+            (None, None) => ExportFormat::Svg,
+        };
+        return match repository {
+            Ok(repository) => export(
+                repository,
+                settings,
+                model.map(|x| &**x),
+                commit_limit,
+                export_format,
+                output.as_deref(),
+            ),
+            Err(err) => Err(format!("ERROR: {}\n       Navigate into a repository before running git-igitt, or use option --path", err.message())),
+        };
+    }
+
+    syntax_highlight::init(&config.syntax_theme, &syntax_dir)?;
+
     run(
         repository.ok(),
         settings,
         app_settings,
         model.map(|x| &**x),
         commit_limit,
+        &filter,
     )
     .map_err(|err| err.to_string())?;
 
@@ -438,11 +639,15 @@ fn run(
     app_settings: AppSettings,
     model: Option<&str>,
     max_commits: Option<usize>,
+    filter: &CommitFilter,
 ) -> Result<(), Box<dyn Error>> {
     enable_raw_mode()?;
 
     let mut sout = stdout();
     execute!(sout, EnterAlternateScreen)?;
+    if app_settings.mouse {
+        execute!(sout, EnableMouseCapture)?;
+    }
 
     let backend = CrosstermBackend::new(sout);
     let mut terminal = Terminal::new(backend)?;
@@ -475,6 +680,7 @@ fn run(
                 &app_settings,
                 model,
                 max_commits,
+                filter,
             )?)
         }
     } else {
@@ -484,6 +690,8 @@ fn run(
     let next_repo_refresh = &Cell::new(Instant::now() + repo_refresh_interval);
     let next_diff_update: &Cell<Option<Instant>> = &Cell::new(None);
     let next_file_update: &Cell<Option<Instant>> = &Cell::new(None);
+    let next_load_check: &Cell<Option<Instant>> = &Cell::new(None);
+    let next_watch_check: &Cell<Option<Instant>> = &Cell::new(None);
     let mut reset_diff_scroll = false;
 
     let mut next_event = {
@@ -498,13 +706,26 @@ fn run(
             if let Some(next) = next_file_update.get() {
                 next_event_time = next.min(next_event_time)
             }
+            if let Some(next) = next_load_check.get() {
+                next_event_time = next.min(next_event_time)
+            }
+            if let Some(next) = next_watch_check.get() {
+                next_event_time = next.min(next_event_time)
+            }
 
             let timeout = next_event_time.saturating_duration_since(Instant::now());
 
             if event::poll(timeout).unwrap() {
                 match event::read().unwrap() {
                     CEvent::Key(key) => return Event::Input(key),
-                    CEvent::Mouse(_) => (),
+                    CEvent::Mouse(mouse) => match mouse.kind {
+                        MouseEventKind::Down(MouseButton::Left)
+                        | MouseEventKind::Up(MouseButton::Left)
+                        | MouseEventKind::Drag(MouseButton::Left)
+                        | MouseEventKind::ScrollDown
+                        | MouseEventKind::ScrollUp => return Event::Mouse(mouse),
+                        _ => {}
+                    },
                     CEvent::Resize(sx, sy) => {
                         if sx != sx_old || sy != sy_old {
                             sx_old = sx;
@@ -528,17 +749,31 @@ fn run(
 
     loop {
         app = if let Some(mut app) = app.take() {
+            if app.is_loading() && next_load_check.get().is_none() {
+                next_load_check.set(Some(
+                    Instant::now() + Duration::from_millis(CHECK_LOADING_RATE),
+                ));
+            }
+            if app.watcher.is_some() && next_watch_check.get().is_none() {
+                next_watch_check.set(Some(
+                    Instant::now() + Duration::from_millis(CHECK_WATCHER_RATE),
+                ));
+            }
             terminal.draw(|f| ui::draw(f, &mut app))?;
             let mut open_file = false;
-            if app.error_message.is_some() {
+            if app.error_message.is_some() || app.conflicts.is_some() {
                 if let Event::Input(event) = next_event() {
                     match event.code {
                         KeyCode::Enter | KeyCode::Esc => {
                             app.clear_error();
                         }
-                        KeyCode::Char('q') => {
+                        _ if app.settings.keymap.action(&event) == Some(Action::Quit) => {
                             disable_raw_mode()?;
-                            execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                            execute!(
+                                terminal.backend_mut(),
+                                LeaveAlternateScreen,
+                                DisableMouseCapture
+                            )?;
                             terminal.show_cursor()?;
                             break;
                         }
@@ -549,14 +784,29 @@ fn run(
             let mut reload_diffs = false;
             let mut reload_file = false;
             let mut reset_scroll = true;
-            if app.active_view == ActiveView::Search {
+            let mut external = Ok(None);
+            if app.active_view == ActiveView::Search
+                || app.active_view == ActiveView::Filter
+                || app.active_view == ActiveView::Dialog
+            {
                 if let Event::Input(event) = next_event() {
                     match event.code {
                         KeyCode::Char(c) => app.character_entered(c),
                         KeyCode::Esc => reload_file = app.on_esc()?,
+                        KeyCode::Enter if app.active_view == ActiveView::Dialog => {
+                            let changed = app.confirm_action();
+                            if changed {
+                                app.reload(&settings, max_commits);
+                            } else {
+                                // E.g. the compare target changed
+                                reload_diffs = true;
+                            }
+                        }
                         KeyCode::Enter | KeyCode::F(3) => {
-                            reload_diffs =
-                                app.on_enter(event.modifiers.contains(KeyModifiers::CONTROL))?
+                            let (rd, rf) =
+                                app.on_enter(event.modifiers.contains(KeyModifiers::CONTROL))?;
+                            reload_diffs = rd;
+                            reload_file = rf;
                         }
                         KeyCode::Backspace => reload_diffs = app.on_backspace()?,
                         _ => {}
@@ -577,20 +827,54 @@ fn run(
                         }
                         last_key_time = now;
 
-                        match event.code {
-                            KeyCode::Char('q') => {
+                        let action = app.settings.keymap.action(&event);
+                        match action {
+                            _ if app.active_view == ActiveView::Models
+                                && matches!(event.code, KeyCode::Enter | KeyCode::Char('p')) =>
+                            {
+                                let (a, s, result) =
+                                    set_app_model(app, settings, max_commits, true)?;
+                                app = a;
+                                settings = s;
+                                if let Err(err) = result {
+                                    app.set_error(err);
+                                    app.active_view = ActiveView::Graph;
+                                }
+                            }
+                            Some(Action::Quit) => {
                                 disable_raw_mode()?;
-                                execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                                execute!(
+                                    terminal.backend_mut(),
+                                    LeaveAlternateScreen,
+                                    DisableMouseCapture
+                                )?;
                                 terminal.show_cursor()?;
                                 break;
                             }
-                            KeyCode::Char('s') => {
+                            Some(Action::ToggleSyntaxHighlight) => {
                                 reload_file = app.toggle_syntax_highlight()?;
                                 reset_scroll = false
                             }
-                            KeyCode::Char('h') => app.show_help(),
-                            KeyCode::F(1) => app.show_help(),
-                            KeyCode::Char('m') => match app.active_view {
+                            Some(Action::Filter) => match app.active_view {
+                                ActiveView::Models | ActiveView::Help(_) => {}
+                                _ => app.open_filter(),
+                            },
+                            Some(Action::ToggleBlame) => {
+                                reload_file = app.toggle_blame()?;
+                                reset_scroll = false
+                            }
+                            Some(Action::Checkout) => app.checkout(),
+                            Some(Action::CompareTo) => app.open_compare(),
+                            Some(Action::CreateBranch) => app.create_branch(),
+                            Some(Action::CreateTag) => app.create_tag(false),
+                            Some(Action::CreateAnnotatedTag) => app.create_tag(true),
+                            Some(Action::DeleteBranch) => app.delete_branch(),
+                            Some(Action::ApplyStash) => app.apply_stash(false),
+                            Some(Action::PopStash) => app.apply_stash(true),
+                            Some(Action::CherryPick) => app.cherry_pick(false),
+                            Some(Action::Revert) => app.cherry_pick(true),
+                            Some(Action::ShowHelp) => app.show_help(),
+                            Some(Action::SelectModel) => match app.active_view {
                                 ActiveView::Models | ActiveView::Search | ActiveView::Help(_) => {}
                                 _ => {
                                     if let Err(err) = app.select_model() {
@@ -598,150 +882,227 @@ fn run(
                                     }
                                 }
                             },
-                            KeyCode::Char('r') => app = app.reload(&settings, max_commits)?,
-                            KeyCode::Char('l') => {
-                                if event.modifiers.contains(KeyModifiers::CONTROL) {
-                                    app.toggle_line_numbers()?;
-                                } else {
-                                    app.toggle_layout();
-                                }
+                            Some(Action::Reload) => app.reload(&settings, max_commits),
+                            Some(Action::ExportGraph) => app.open_export(),
+                            Some(Action::Yank) => app.yank(false),
+                            Some(Action::YankShort) => app.yank(true),
+                            Some(Action::OpenEditor) => external = app.editor_command(),
+                            Some(Action::DiffTool) => external = app.difftool_command(),
+                            Some(Action::Pager) => external = app.pager_command(),
+                            Some(Action::ToggleLineNumbers) => {
+                                app.toggle_line_numbers()?;
                             }
-                            KeyCode::Char('w') => {
-                                if event.modifiers.contains(KeyModifiers::CONTROL) {
-                                    app.toggle_line_wrap()?;
+                            Some(Action::ToggleLayout) => app.toggle_layout(),
+                            Some(Action::ToggleLineWrap) => {
+                                app.toggle_line_wrap()?;
+                            }
+                            Some(Action::ToggleWordDiff) => {
+                                app.toggle_word_diff()?;
+                            }
+                            Some(Action::ToggleFileTree) => {
+                                app.toggle_file_tree()?;
+                            }
+                            Some(Action::ToggleDirectory) => {
+                                reload_file = app.toggle_directory()?;
+                            }
+                            Some(Action::ToggleBranches) => app.toggle_branches(),
+                            Some(Action::ToggleReflog) => {
+                                if let Err(err) = app.toggle_reflog() {
+                                    app.set_error(err);
                                 }
+                                reload_file = true;
                             }
-                            KeyCode::Char('b') => app.toggle_branches(),
-                            KeyCode::Char('o') => match app.active_view {
+                            Some(Action::OpenRepository) => match app.active_view {
                                 ActiveView::Models | ActiveView::Search | ActiveView::Help(_) => {}
                                 _ => {
-                                    if event.modifiers.contains(KeyModifiers::CONTROL) {
-                                        if let Some(graph) = &app.graph_state.graph {
-                                            let path = graph.repository.path();
-                                            let path = path.parent().unwrap_or(path);
-                                            file_dialog.location =
-                                                PathBuf::from(path.parent().unwrap_or(path));
-                                            file_dialog.selection = Some(PathBuf::from(path));
-                                        } else {
-                                            file_dialog.location = std::env::current_dir()?;
-                                            file_dialog.selection = None
-                                        }
-                                        open_file = true;
+                                    if let Some(graph) = &app.graph_state.graph {
+                                        let path = graph.repository.path();
+                                        let path = path.parent().unwrap_or(path);
+                                        file_dialog.location =
+                                            PathBuf::from(path.parent().unwrap_or(path));
+                                        file_dialog.selection = Some(PathBuf::from(path));
                                     } else {
-                                        let reset = app.diff_options.diff_mode == DiffMode::Diff;
-                                        reload_file = app.set_diff_mode(DiffMode::Old)?;
-                                        reset_scroll = reset;
+                                        file_dialog.location = std::env::current_dir()?;
+                                        file_dialog.selection = None
                                     }
+                                    open_file = true;
                                 }
                             },
-                            KeyCode::Char('f')
-                                if event.modifiers.contains(KeyModifiers::CONTROL) =>
-                            {
-                                match app.active_view {
-                                    ActiveView::Models
-                                    | ActiveView::Search
-                                    | ActiveView::Help(_) => {}
-                                    _ => app.open_search(),
+                            Some(Action::ShowOld) => match app.active_view {
+                                ActiveView::Models | ActiveView::Search | ActiveView::Help(_) => {}
+                                _ => {
+                                    let reset = app.diff_options.diff_mode.is_patch();
+                                    reload_file = app.set_diff_mode(DiffMode::Old)?;
+                                    reset_scroll = reset;
                                 }
-                            }
-                            KeyCode::F(3) => match app.active_view {
+                            },
+                            Some(Action::OpenSearch) => match app.active_view {
+                                ActiveView::Models | ActiveView::Search | ActiveView::Help(_) => {}
+                                _ => app.open_search(),
+                            },
+                            Some(Action::SearchNext) => match app.active_view {
                                 ActiveView::Models | ActiveView::Search | ActiveView::Help(_) => {}
                                 _ => {
                                     if app.search_term.is_none() {
                                         app.open_search();
                                     } else {
-                                        reload_diffs = app.search()?
+                                        let (rd, rf) = app.search()?;
+                                        reload_diffs = rd;
+                                        reload_file = rf;
                                     }
                                 }
                             },
-                            KeyCode::Char('n') => {
-                                let reset = app.diff_options.diff_mode == DiffMode::Diff;
+                            Some(Action::ShowNew) => {
+                                let reset = app.diff_options.diff_mode.is_patch();
                                 reload_file = app.set_diff_mode(DiffMode::New)?;
                                 reset_scroll = reset;
                             }
-                            KeyCode::Char('d') => {
+                            Some(Action::ShowDiff) => {
+                                let reset = !app.diff_options.diff_mode.is_patch();
                                 reload_file = app.set_diff_mode(DiffMode::Diff)?;
+                                reset_scroll = reset;
                             }
-                            KeyCode::Char('p') => {
-                                if app.active_view == ActiveView::Models {
-                                    let (a, s, result) =
-                                        set_app_model(app, settings, max_commits, true)?;
-                                    app = a;
-                                    settings = s;
-                                    if let Err(err) = result {
-                                        app.set_error(err);
-                                        app.active_view = ActiveView::Graph;
-                                    }
-                                }
+                            Some(Action::ShowSplit) => {
+                                let reset = !app.diff_options.diff_mode.is_patch();
+                                reload_file = app.set_diff_mode(DiffMode::Split)?;
+                                reset_scroll = reset;
                             }
-
-                            KeyCode::Char('+') => {
+                            Some(Action::FileHistory) if app.active_view == ActiveView::Files => {
+                                reload_diffs = app.toggle_file_history()?;
+                            }
+                            Some(Action::MoreContext) => {
                                 reload_file = app.on_plus()?;
                                 reset_scroll = false;
                             }
-                            KeyCode::Char('-') => {
+                            Some(Action::LessContext) => {
                                 reload_file = app.on_minus()?;
                                 reset_scroll = false;
                             }
-
-                            KeyCode::Up => {
+                            Some(
+                                action @ (Action::Up
+                                | Action::UpFast
+                                | Action::SecondaryUp
+                                | Action::SecondaryUpFast),
+                            ) => {
                                 let (rd, rf) = app.on_up(
-                                    event.modifiers.contains(KeyModifiers::SHIFT),
-                                    event.modifiers.contains(KeyModifiers::CONTROL),
+                                    matches!(action, Action::UpFast | Action::SecondaryUpFast),
+                                    matches!(action, Action::SecondaryUp | Action::SecondaryUpFast),
                                 )?;
                                 reload_diffs = rd;
                                 reload_file = rf;
                             }
-                            KeyCode::Down => {
+                            Some(
+                                action @ (Action::Down
+                                | Action::DownFast
+                                | Action::SecondaryDown
+                                | Action::SecondaryDownFast),
+                            ) => {
                                 let (rd, rf) = app.on_down(
-                                    event.modifiers.contains(KeyModifiers::SHIFT),
-                                    event.modifiers.contains(KeyModifiers::CONTROL),
+                                    matches!(action, Action::DownFast | Action::SecondaryDownFast),
+                                    matches!(
+                                        action,
+                                        Action::SecondaryDown | Action::SecondaryDownFast
+                                    ),
                                 )?;
                                 reload_diffs = rd;
                                 reload_file = rf;
                             }
-                            KeyCode::Home => reload_diffs = app.on_home()?,
-                            KeyCode::End => reload_diffs = app.on_end()?,
-                            KeyCode::Left => app.on_left(
-                                event.modifiers.contains(KeyModifiers::SHIFT),
-                                event.modifiers.contains(KeyModifiers::CONTROL),
+                            Some(Action::Home) => reload_diffs = app.on_home()?,
+                            Some(Action::End) => reload_diffs = app.on_end()?,
+                            Some(
+                                action @ (Action::PreviousPanel
+                                | Action::ScrollLeft
+                                | Action::ScrollLeftFast),
+                            ) => app.on_left(
+                                action == Action::ScrollLeftFast,
+                                action != Action::PreviousPanel,
                             ),
-                            KeyCode::Right => {
+                            Some(
+                                action @ (Action::NextPanel
+                                | Action::ScrollRight
+                                | Action::ScrollRightFast),
+                            ) => {
                                 reload_file = app.on_right(
-                                    event.modifiers.contains(KeyModifiers::SHIFT),
-                                    event.modifiers.contains(KeyModifiers::CONTROL),
+                                    action == Action::ScrollRightFast,
+                                    action != Action::NextPanel,
                                 )?
                             }
-                            KeyCode::Tab => app.on_tab(),
-                            KeyCode::Esc => reload_file = app.on_esc()?,
-                            KeyCode::Enter => {
-                                if app.active_view == ActiveView::Models {
-                                    let (a, s, result) =
-                                        set_app_model(app, settings, max_commits, true)?;
-                                    app = a;
-                                    settings = s;
-                                    if let Err(err) = result {
-                                        app.set_error(err);
-                                        app.active_view = ActiveView::Graph;
-                                    }
-                                } else {
-                                    reload_diffs = app
-                                        .on_enter(event.modifiers.contains(KeyModifiers::CONTROL))?
-                                }
+                            Some(Action::Fullscreen) => app.on_tab(),
+                            Some(Action::DefaultView) => reload_file = app.on_esc()?,
+                            Some(action @ (Action::Confirm | Action::SecondaryConfirm)) => {
+                                let (rd, rf) = app.on_enter(action == Action::SecondaryConfirm)?;
+                                reload_diffs = rd;
+                                reload_file = rf;
                             }
-                            KeyCode::Backspace => {
-                                if app.active_view != ActiveView::Models {
-                                    reload_diffs = app.on_backspace()?
-                                }
+                            Some(Action::ClearSecondary)
+                                if app.active_view != ActiveView::Models =>
+                            {
+                                reload_diffs = app.on_backspace()?
                             }
                             _ => {}
                         }
                     }
+                    Event::Mouse(mouse) => match mouse.kind {
+                        MouseEventKind::Down(_) => {
+                            let (rd, rf) = app.on_click(mouse.column, mouse.row)?;
+                            reload_diffs = rd;
+                            reload_file = rf;
+                        }
+                        MouseEventKind::Drag(_) => app.on_drag(mouse.column, mouse.row),
+                        MouseEventKind::Up(_) => app.on_release(),
+                        MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                            let (rd, rf) = app.on_scroll(
+                                mouse.column,
+                                mouse.row,
+                                mouse.kind == MouseEventKind::ScrollDown,
+                            )?;
+                            reload_diffs = rd;
+                            reload_file = rf;
+                            reset_scroll = false;
+                        }
+                        _ => {}
+                    },
                     Event::Update => {
                         let now = Instant::now();
+                        if let Some(next) = next_load_check.get() {
+                            if next <= now {
+                                app.poll_graph()?;
+                                if let (false, Some(graph)) =
+                                    (app.is_loading(), &app.graph_state.graph)
+                                {
+                                    app.curr_branches = get_references(graph)?;
+                                }
+                                next_load_check.set(None);
+                            }
+                        }
+                        if let Some(next) = next_watch_check.get() {
+                            if next <= now {
+                                if !app.is_loading() {
+                                    let changes = app.watcher.as_mut().and_then(|w| w.poll());
+                                    if let Some(changes) = changes {
+                                        apply_changes(&mut app, changes, &settings, max_commits)?;
+                                    }
+                                }
+                                next_watch_check.set(None);
+                            }
+                        }
                         if next_repo_refresh.get() <= now {
-                            if app.graph_state.graph.is_some() && has_changed(&mut app)? {
-                                app = app.reload(&settings, max_commits)?;
+                            if app.graph_state.graph.is_some() && !app.is_loading() {
+                                // Polls whatever the watcher does not report
+                                let changes = match &app.watcher {
+                                    Some(watcher) => Changes {
+                                        worktree: !watcher.watches_worktree(),
+                                        ..Changes::default()
+                                    },
+                                    None => Changes {
+                                        refs: true,
+                                        index: false,
+                                        worktree: true,
+                                    },
+                                };
+                                if changes.any() {
+                                    apply_changes(&mut app, changes, &settings, max_commits)?;
+                                }
                             }
                             next_repo_refresh.set(now + repo_refresh_interval);
                         }
@@ -760,6 +1121,16 @@ fn run(
                     }
                 }
             };
+            match external {
+                Ok(Some(command)) => {
+                    if let Err(err) = run_external(&mut terminal, command, app.settings.mouse)? {
+                        app.set_error(err);
+                    }
+                    app.reload(&settings, max_commits);
+                }
+                Ok(None) => {}
+                Err(err) => app.set_error(err),
+            }
             if reload_diffs {
                 app.reload_diff_message()?;
                 next_diff_update.set(Some(
@@ -789,7 +1160,14 @@ fn run(
                 Some(app)
             }
         } else {
-            terminal.draw(|f| ui::draw_open_repo(f, &mut file_dialog))?;
+            terminal.draw(|f| {
+                ui::draw_open_repo(
+                    f,
+                    &mut file_dialog,
+                    &app_settings.theme,
+                    &app_settings.keymap,
+                )
+            })?;
 
             let mut app = None;
             if file_dialog.error_message.is_some() {
@@ -798,9 +1176,13 @@ fn run(
                         KeyCode::Enter | KeyCode::Esc => {
                             file_dialog.clear_error();
                         }
-                        KeyCode::Char('q') => {
+                        _ if app_settings.keymap.action(&event) == Some(Action::Quit) => {
                             disable_raw_mode()?;
-                            execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                            execute!(
+                                terminal.backend_mut(),
+                                LeaveAlternateScreen,
+                                DisableMouseCapture
+                            )?;
                             terminal.show_cursor()?;
                             break;
                         }
@@ -808,25 +1190,26 @@ fn run(
                     }
                 }
             } else if let Event::Input(event) = next_event() {
+                let action = app_settings.keymap.action(&event);
                 match event.code {
-                    KeyCode::Char('q') => {
+                    _ if action == Some(Action::Quit) => {
                         disable_raw_mode()?;
-                        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                        execute!(
+                            terminal.backend_mut(),
+                            LeaveAlternateScreen,
+                            DisableMouseCapture
+                        )?;
                         terminal.show_cursor()?;
                         break;
                     }
-                    KeyCode::Char('o') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    _ if action == Some(Action::OpenRepository) || event.code == KeyCode::Esc => {
                         if let Some(prev_app) = file_dialog.previous_app.take() {
                             app = Some(prev_app);
                         } else {
-                            file_dialog.set_error("No repository to return to.\nSelect a Git rrpository or quit with Q.".to_string())
-                        }
-                    }
-                    KeyCode::Esc => {
-                        if let Some(prev_app) = file_dialog.previous_app.take() {
-                            app = Some(prev_app);
-                        } else {
-                            file_dialog.set_error("No repository to return to.\nSelect a Git rrpository or quit with Q.".to_string())
+                            file_dialog.set_error(format!(
+                                "No repository to return to.\nSelect a Git repository or quit with {}.",
+                                app_settings.keymap.key_name(Action::Quit)
+                            ))
                         }
                     }
                     KeyCode::Up => file_dialog.on_up(event.modifiers.contains(KeyModifiers::SHIFT)),
@@ -849,6 +1232,7 @@ fn run(
                                             &app_settings,
                                             model,
                                             max_commits,
+                                            filter,
                                         )?)
                                     }
                                 }
@@ -868,6 +1252,32 @@ fn run(
     Ok(())
 }
 
+/// Runs an external program in the terminal, with the UI suspended until the program exits
+fn run_external(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    command: ExternalCommand,
+    mouse: bool,
+) -> Result<Result<(), String>, Box<dyn Error>> {
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    let result = command.run();
+
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    if mouse {
+        execute!(terminal.backend_mut(), EnableMouseCapture)?;
+    }
+    terminal.hide_cursor()?;
+    terminal.clear()?;
+    Ok(result)
+}
+
 fn set_app_model(
     mut app: App,
     mut settings: Settings,
@@ -918,7 +1328,7 @@ fn set_app_model(
                     ))
                 }
             };
-            app = app.reload(&settings, max_commits)?;
+            app.reload(&settings, max_commits);
         }
     }
     Ok((app, settings, Ok(())))
@@ -961,12 +1371,90 @@ pub fn set_model<P: AsRef<Path>>(
     Ok(())
 }
 
+/// Builds the graph of a repository using its branching model, and prints it to lines
+fn build_graph(
+    repository: Repository,
+    settings: &mut Settings,
+    model: Option<&str>,
+    max_commits: Option<usize>,
+) -> Result<(GitGraph, Vec<String>, Vec<String>), String> {
+    let app_dir = AppDirs::new(Some("git-graph"), false).unwrap().config_dir;
+    let mut models_dir = app_dir;
+    models_dir.push("models");
+
+    let the_model = get_model(&repository, model, REPO_CONFIG_FILE, &models_dir)?;
+    settings.branches = BranchSettings::from(the_model).map_err(|err| err.to_string())?;
+
+    let graph = GitGraph::new(repository, settings, max_commits)?;
+    let (graph_lines, text_lines, _) = print_unicode(&graph, settings)?;
+    Ok((graph, graph_lines, text_lines))
+}
+
+/// Prints the graph of a repository to stdout
+fn print_graph(
+    repository: Repository,
+    mut settings: Settings,
+    model: Option<&str>,
+    max_commits: Option<usize>,
+) -> Result<(), String> {
+    let (_, graph_lines, text_lines) = build_graph(repository, &mut settings, model, max_commits)?;
+
+    let mut out = stdout().lock();
+    for (graph_line, text_line) in graph_lines.iter().zip(text_lines.iter()) {
+        match writeln!(out, " {}  {}", graph_line, text_line) {
+            Ok(()) => {}
+            // The output was closed early, e.g. by a pager
+            Err(err) if err.kind() == ErrorKind::BrokenPipe => return Ok(()),
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Exports the graph of a repository to a file, or to stdout if no file is given
+fn export(
+    repository: Repository,
+    mut settings: Settings,
+    model: Option<&str>,
+    max_commits: Option<usize>,
+    format: ExportFormat,
+    output: Option<&Path>,
+) -> Result<(), String> {
+    // Colors are always exported, independent of the terminal
+    yansi::enable();
+    settings.colored = true;
+
+    let (graph, graph_lines, text_lines) =
+        build_graph(repository, &mut settings, model, max_commits)?;
+    let title = format!(
+        "git-igitt - {}",
+        graph
+            .repository
+            .workdir()
+            .unwrap_or_else(|| graph.repository.path())
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("unknown")
+    );
+    let content = export_graph(&graph_lines, &text_lines, format, &title);
+
+    match output {
+        Some(path) => std::fs::write(path, content)
+            .map_err(|err| format!("Unable to write file {}\n{}", path.display(), err)),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
+
 fn create_app(
     repository: Repository,
     settings: &mut Settings,
     app_settings: &AppSettings,
     model: Option<&str>,
     max_commits: Option<usize>,
+    filter: &CommitFilter,
 ) -> Result<App, String> {
     let app_dir = AppDirs::new(Some("git-graph"), false).unwrap().config_dir;
     let mut models_dir = app_dir;
@@ -986,56 +1474,110 @@ fn create_app(
         .unwrap_or("unknown")
         .to_string();
 
-    let graph = GitGraph::new(repository, settings, max_commits)?;
-    let branches = get_branches(&graph)?;
-    let (graph_lines, text_lines, indices) = print_unicode(&graph, settings)?;
-
-    Ok(App::new(
+    let mut app = App::new(
         app_settings.clone(),
         format!("git-igitt - {}", name),
         name.clone(),
         models_dir,
     )
-    .with_graph(graph, graph_lines, text_lines, indices, true)?
-    .with_branches(branches)
-    .with_color(settings.colored))
+    .with_color(settings.colored)
+    .with_filter(filter.clone());
+    app.load(repository.path().to_path_buf(), settings, max_commits, true);
+    // Without a watcher, the repository is polled for changes
+    app.watcher = RepoWatcher::new(repository.path(), repository.workdir()).ok();
+    Ok(app)
 }
 
-fn has_changed(app: &mut App) -> Result<bool, String> {
-    if let Some(graph) = &app.graph_state.graph {
-        let branches = get_branches(graph)?;
-
-        if app.curr_branches != branches {
-            app.curr_branches = branches;
-            return Ok(true);
+/// Reloads the parts of the app affected by changes of the repository.
+/// Reported changes are checked first, as e.g. rewriting `packed-refs` may change nothing.
+fn apply_changes(
+    app: &mut App,
+    changes: Changes,
+    settings: &Settings,
+    max_commits: Option<usize>,
+) -> Result<(), String> {
+    if changes.refs {
+        match changed_refs(app, settings)? {
+            RefChange::Graph => {
+                app.reload(settings, max_commits);
+                return Ok(());
+            }
+            RefChange::Stashes => app.reload_stashes()?,
+            RefChange::None => {}
         }
+    }
+    if changes.index || (changes.worktree && pseudo_commits_changed(app)?) {
+        app.reload_pseudo_commits()?;
+    }
+    Ok(())
+}
 
-        let head = graph
-            .repository
-            .head()
-            .map_err(|err| err.message().to_string())?;
-
-        let name = head.name().ok_or_else(|| "No name for HEAD".to_string())?;
-        let name = if name == "HEAD" { name } else { &name[11..] };
-        if graph.head.name != name
-            || graph.head.oid != head.target().ok_or_else(|| "No id for HEAD".to_string())?
-            || graph.head.is_branch != head.is_branch()
-        {
-            return Ok(true);
+/// What needs to be reloaded after references changed
+enum RefChange {
+    None,
+    /// Only the stashes in the branch list
+    Stashes,
+    /// The graph, as branches, tags or HEAD changed
+    Graph,
+}
+
+/// Compares the references to those the graph was loaded with
+fn changed_refs(app: &mut App, settings: &Settings) -> Result<RefChange, String> {
+    let graph = match &app.graph_state.graph {
+        Some(graph) => graph,
+        None => return Ok(RefChange::None),
+    };
+    let head = graph
+        .repository
+        .head()
+        .map_err(|err| err.message().to_string())?;
+    let name = head.name().ok_or_else(|| "No name for HEAD".to_string())?;
+    let name = if name == "HEAD" { name } else { &name[11..] };
+    if graph.head.name != name
+        || graph.head.oid != head.target().ok_or_else(|| "No id for HEAD".to_string())?
+        || graph.head.is_branch != head.is_branch()
+    {
+        return Ok(RefChange::Graph);
+    }
+
+    let references = get_references(graph)?;
+    let old: HashSet<_> = app.curr_branches.iter().collect();
+    let new: HashSet<_> = references.iter().collect();
+    let mut change = RefChange::None;
+    for (name, _) in old.symmetric_difference(&new) {
+        match name.as_deref() {
+            Some("refs/stash") => change = RefChange::Stashes,
+            // Remote branches are not shown
+            Some(name) if name.starts_with("refs/remotes/") && !settings.include_remote => {}
+            _ => {
+                change = RefChange::Graph;
+                break;
+            }
         }
     }
-    Ok(false)
+    app.curr_branches = references;
+    Ok(change)
+}
+
+fn pseudo_commits_changed(app: &App) -> Result<bool, String> {
+    match &app.graph_state.graph {
+        Some(graph) => Ok(app.graph_state.pseudo_commits != get_pseudo_commits(&graph.repository)?),
+        None => Ok(false),
+    }
 }
 
-fn get_branches(graph: &GitGraph) -> Result<CurrentBranches, String> {
+/// All references (branches, remotes, tags, stashes) with their targets
+fn get_references(graph: &GitGraph) -> Result<CurrentBranches, String> {
     graph
         .repository
-        .branches(None)
+        .references()
         .map_err(|err| err.message().to_string())?
-        .map(|br| {
-            br.and_then(|(br, _tp)| {
-                br.name()
-                    .map(|n| (n.map(|n| n.to_string()), br.get().target()))
+        .map(|reference| {
+            reference.map(|reference| {
+                (
+                    reference.name().map(|name| name.to_string()),
+                    reference.target(),
+                )
             })
         })
         .collect::<Result<CurrentBranches, _>>()
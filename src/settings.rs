@@ -1,12 +1,32 @@
+use crate::app::DiffOptions;
+use crate::config::Config;
+use crate::keymap::Keymap;
+use crate::theme::Theme;
+
 #[derive(Clone)]
 pub struct AppSettings {
     pub tab_spaces: String,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    pub horizontal_split: bool,
+    pub show_branches: bool,
+    pub diff_options: DiffOptions,
+    pub mouse: bool,
+    /// Command to copy to the clipboard, or empty to use the terminal
+    pub clipboard_command: String,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
             tab_spaces: "    ".to_string(),
+            keymap: Keymap::default(),
+            theme: Theme::default(),
+            horizontal_split: true,
+            show_branches: false,
+            diff_options: DiffOptions::default(),
+            mouse: true,
+            clipboard_command: String::new(),
         }
     }
 }
@@ -16,4 +36,24 @@ impl AppSettings {
         self.tab_spaces = " ".repeat(width);
         self
     }
+
+    pub fn keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Takes the layout, diff, mouse and clipboard settings from the user configuration
+    pub fn config(mut self, config: &Config) -> Self {
+        self.horizontal_split = config.horizontal_split;
+        self.show_branches = config.show_branches;
+        self.diff_options = config.diff.clone();
+        self.mouse = config.mouse;
+        self.clipboard_command = config.clipboard_command.clone();
+        self.tab_width(config.tab_width)
+    }
 }
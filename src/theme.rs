@@ -0,0 +1,492 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tui::style::{Color, Modifier, Style};
+
+/// Names of the built-in themes
+pub const THEMES: [&str; 3] = ["dark", "light", "high-contrast"];
+
+/// A text style of a theme. Missing colors keep the terminal's colors.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeStyle {
+    #[serde(with = "color_option", skip_serializing_if = "Option::is_none")]
+    pub fg: Option<Color>,
+    #[serde(with = "color_option", skip_serializing_if = "Option::is_none")]
+    pub bg: Option<Color>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<ThemeModifier>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeModifier {
+    Bold,
+    Dim,
+    Italic,
+    Underlined,
+    Reversed,
+}
+
+impl ThemeStyle {
+    fn fg(color: Color) -> Self {
+        ThemeStyle {
+            fg: Some(color),
+            ..Default::default()
+        }
+    }
+
+    fn bg(mut self, color: Color) -> Self {
+        self.bg = Some(color);
+        self
+    }
+
+    fn modifier(mut self, modifier: ThemeModifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    pub fn style(&self) -> Style {
+        let mut style = Style::default();
+        if let Some(fg) = self.fg {
+            style = style.fg(fg);
+        }
+        if let Some(bg) = self.bg {
+            style = style.bg(bg);
+        }
+        for modifier in &self.modifiers {
+            style = style.add_modifier(match modifier {
+                ThemeModifier::Bold => Modifier::BOLD,
+                ThemeModifier::Dim => Modifier::DIM,
+                ThemeModifier::Italic => Modifier::ITALIC,
+                ThemeModifier::Underlined => Modifier::UNDERLINED,
+                ThemeModifier::Reversed => Modifier::REVERSED,
+            });
+        }
+        style
+    }
+}
+
+/// Colors and styles of the user interface.
+///
+/// Colors are names like `light-green`, hex codes like `#ff8800`, or terminal color indices.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Borders of inactive panels
+    pub border: ThemeStyle,
+    /// Borders of the active panel, in addition to the thick border
+    pub active_border: ThemeStyle,
+    /// Selected rows of the graph and lists
+    pub selection: ThemeStyle,
+    /// Navigation hints in panel titles
+    pub hint: ThemeStyle,
+    /// Commits not matching the filter
+    pub filtered: ThemeStyle,
+    /// Commit hashes and blame annotations
+    #[serde(with = "color")]
+    pub hash: Color,
+    /// Matches of the search term in the diff
+    pub search: ThemeStyle,
+    /// Repositories in the dialog for opening a repository
+    pub repository: ThemeStyle,
+    /// Border of the error dialog
+    pub error: ThemeStyle,
+    /// Border of the conflicts dialog
+    pub warning: ThemeStyle,
+    /// Conflicted files in the conflicts dialog
+    pub conflict: ThemeStyle,
+    /// Status letters and paths of files in the files panel
+    pub file_added: ThemeStyle,
+    pub file_deleted: ThemeStyle,
+    pub file_modified: ThemeStyle,
+    pub file_renamed: ThemeStyle,
+    /// Directories in the file tree of the files panel
+    pub directory: ThemeStyle,
+    /// Section headings of the branch list
+    pub branch_heading: ThemeStyle,
+    /// Stashes in the branch list
+    pub stash: ThemeStyle,
+    /// Lines of diffs
+    pub diff_added: ThemeStyle,
+    pub diff_removed: ThemeStyle,
+    pub diff_hunk_header: ThemeStyle,
+    /// Changed words in added and removed lines
+    pub diff_added_word: ThemeStyle,
+    pub diff_removed_word: ThemeStyle,
+    /// Backgrounds of added and removed lines and words with syntax highlighting
+    #[serde(with = "color")]
+    pub syntax_added_bg: Color,
+    #[serde(with = "color")]
+    pub syntax_removed_bg: Color,
+    #[serde(with = "color")]
+    pub syntax_added_word_bg: Color,
+    #[serde(with = "color")]
+    pub syntax_removed_word_bg: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            border: ThemeStyle::default(),
+            active_border: ThemeStyle::default(),
+            selection: ThemeStyle::default().modifier(ThemeModifier::Underlined),
+            hint: ThemeStyle::fg(Color::Cyan),
+            filtered: ThemeStyle::fg(Color::DarkGray),
+            hash: Color::Indexed(11),
+            search: ThemeStyle::fg(Color::Black).bg(Color::Yellow),
+            repository: ThemeStyle::fg(Color::LightGreen),
+            error: ThemeStyle::fg(Color::LightRed),
+            warning: ThemeStyle::fg(Color::LightYellow),
+            conflict: ThemeStyle::fg(Color::LightRed),
+            file_added: ThemeStyle::fg(Color::LightGreen),
+            file_deleted: ThemeStyle::fg(Color::LightRed),
+            file_modified: ThemeStyle::fg(Color::LightYellow),
+            file_renamed: ThemeStyle::fg(Color::LightBlue),
+            directory: ThemeStyle::default().modifier(ThemeModifier::Bold),
+            branch_heading: ThemeStyle::fg(Color::Gray),
+            stash: ThemeStyle::fg(Color::Gray),
+            diff_added: ThemeStyle::fg(Color::LightGreen),
+            diff_removed: ThemeStyle::fg(Color::LightRed),
+            diff_hunk_header: ThemeStyle::fg(Color::LightBlue),
+            diff_added_word: ThemeStyle::fg(Color::Black).bg(Color::LightGreen),
+            diff_removed_word: ThemeStyle::fg(Color::Black).bg(Color::LightRed),
+            syntax_added_bg: Color::Rgb(0, 60, 0),
+            syntax_removed_bg: Color::Rgb(80, 0, 0),
+            syntax_added_word_bg: Color::Rgb(0, 120, 0),
+            syntax_removed_word_bg: Color::Rgb(150, 0, 0),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            border: ThemeStyle::default(),
+            active_border: ThemeStyle::fg(Color::Blue),
+            selection: ThemeStyle::default().bg(Color::Rgb(210, 225, 245)),
+            hint: ThemeStyle::fg(Color::Blue),
+            filtered: ThemeStyle::fg(Color::Gray),
+            hash: Color::Rgb(150, 100, 0),
+            search: ThemeStyle::fg(Color::Black).bg(Color::LightYellow),
+            repository: ThemeStyle::fg(Color::Green),
+            error: ThemeStyle::fg(Color::Red),
+            warning: ThemeStyle::fg(Color::Rgb(180, 120, 0)),
+            conflict: ThemeStyle::fg(Color::Red),
+            file_added: ThemeStyle::fg(Color::Green),
+            file_deleted: ThemeStyle::fg(Color::Red),
+            file_modified: ThemeStyle::fg(Color::Rgb(180, 120, 0)),
+            file_renamed: ThemeStyle::fg(Color::Blue),
+            directory: ThemeStyle::default().modifier(ThemeModifier::Bold),
+            branch_heading: ThemeStyle::fg(Color::Black),
+            stash: ThemeStyle::fg(Color::DarkGray),
+            diff_added: ThemeStyle::fg(Color::Green),
+            diff_removed: ThemeStyle::fg(Color::Red),
+            diff_hunk_header: ThemeStyle::fg(Color::Blue),
+            diff_added_word: ThemeStyle::fg(Color::Black).bg(Color::Rgb(150, 230, 150)),
+            diff_removed_word: ThemeStyle::fg(Color::Black).bg(Color::Rgb(245, 160, 160)),
+            syntax_added_bg: Color::Rgb(220, 250, 220),
+            syntax_removed_bg: Color::Rgb(255, 225, 225),
+            syntax_added_word_bg: Color::Rgb(160, 235, 160),
+            syntax_removed_word_bg: Color::Rgb(250, 170, 170),
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            border: ThemeStyle::fg(Color::White),
+            active_border: ThemeStyle::fg(Color::LightYellow).modifier(ThemeModifier::Bold),
+            selection: ThemeStyle::default().modifier(ThemeModifier::Reversed),
+            hint: ThemeStyle::fg(Color::LightCyan).modifier(ThemeModifier::Bold),
+            filtered: ThemeStyle::fg(Color::Gray).modifier(ThemeModifier::Dim),
+            hash: Color::LightYellow,
+            search: ThemeStyle::fg(Color::Black)
+                .bg(Color::LightYellow)
+                .modifier(ThemeModifier::Bold),
+            repository: ThemeStyle::fg(Color::LightGreen).modifier(ThemeModifier::Bold),
+            error: ThemeStyle::fg(Color::LightRed).modifier(ThemeModifier::Bold),
+            warning: ThemeStyle::fg(Color::LightYellow).modifier(ThemeModifier::Bold),
+            conflict: ThemeStyle::fg(Color::LightRed).modifier(ThemeModifier::Bold),
+            file_added: ThemeStyle::fg(Color::LightGreen).modifier(ThemeModifier::Bold),
+            file_deleted: ThemeStyle::fg(Color::LightRed).modifier(ThemeModifier::Bold),
+            file_modified: ThemeStyle::fg(Color::LightYellow).modifier(ThemeModifier::Bold),
+            file_renamed: ThemeStyle::fg(Color::LightCyan).modifier(ThemeModifier::Bold),
+            directory: ThemeStyle::fg(Color::White).modifier(ThemeModifier::Bold),
+            branch_heading: ThemeStyle::fg(Color::White).modifier(ThemeModifier::Bold),
+            stash: ThemeStyle::fg(Color::White),
+            diff_added: ThemeStyle::fg(Color::LightGreen),
+            diff_removed: ThemeStyle::fg(Color::LightRed),
+            diff_hunk_header: ThemeStyle::fg(Color::LightCyan).modifier(ThemeModifier::Bold),
+            diff_added_word: ThemeStyle::fg(Color::Black)
+                .bg(Color::LightGreen)
+                .modifier(ThemeModifier::Bold),
+            diff_removed_word: ThemeStyle::fg(Color::Black)
+                .bg(Color::LightRed)
+                .modifier(ThemeModifier::Bold),
+            syntax_added_bg: Color::Rgb(0, 80, 0),
+            syntax_removed_bg: Color::Rgb(110, 0, 0),
+            syntax_added_word_bg: Color::Rgb(0, 160, 0),
+            syntax_removed_word_bg: Color::Rgb(190, 0, 0),
+        }
+    }
+
+    /// A built-in theme by name
+    pub fn built_in(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Loads a built-in theme, or the theme file `<name>.toml` from the given directory.
+    ///
+    /// Theme files override the entries of the built-in theme given by `base`, or `dark`.
+    pub fn load(name: &str, dir: &Path) -> Result<Self, String> {
+        if let Some(theme) = Self::built_in(name) {
+            return Ok(theme);
+        }
+        let path = dir.join(format!("{}.toml", name));
+        if !path.exists() {
+            return Err(format!(
+                "Unknown theme '{}'. Supports [{}], or a theme file {}",
+                name,
+                THEMES.join("|"),
+                path.display()
+            ));
+        }
+        let error = |err: &dyn std::fmt::Display| {
+            format!("Error in theme file {}\n{}", path.display(), err)
+        };
+        let content = std::fs::read_to_string(&path).map_err(|err| error(&err))?;
+        let mut entries: toml::Table = toml::from_str(&content).map_err(|err| error(&err))?;
+
+        let base = match entries.remove("base") {
+            Some(toml::Value::String(base)) => Self::built_in(&base).ok_or_else(|| {
+                error(&format!(
+                    "Unknown base theme '{}'. Supports [{}].",
+                    base,
+                    THEMES.join("|")
+                ))
+            })?,
+            Some(_) => return Err(error(&"Entry 'base' must be a string")),
+            None => Self::dark(),
+        };
+        let mut theme = toml::Table::try_from(&base).map_err(|err| error(&err))?;
+        theme.extend(entries);
+        theme.try_into().map_err(|err| error(&err))
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string_pretty(self).map_err(|err| err.to_string())
+    }
+
+    /// The hash color as a terminal color index, for text formatted with escape codes
+    pub fn hash_index(&self) -> u8 {
+        match self.hash {
+            Color::Black => 0,
+            Color::Red => 1,
+            Color::Green => 2,
+            Color::Yellow => 3,
+            Color::Blue => 4,
+            Color::Magenta => 5,
+            Color::Cyan => 6,
+            Color::Gray | Color::Reset => 7,
+            Color::DarkGray => 8,
+            Color::LightRed => 9,
+            Color::LightGreen => 10,
+            Color::LightYellow => 11,
+            Color::LightBlue => 12,
+            Color::LightMagenta => 13,
+            Color::LightCyan => 14,
+            Color::White => 15,
+            Color::Indexed(index) => index,
+            // The closest color of the 6x6x6 color cube
+            Color::Rgb(r, g, b) => {
+                let level = |value: u8| ((value as u16 * 5 + 127) / 255) as u8;
+                16 + 36 * level(r) + 6 * level(g) + level(b)
+            }
+        }
+    }
+}
+
+const COLOR_NAMES: [(Color, &str); 17] = [
+    (Color::Reset, "reset"),
+    (Color::Black, "black"),
+    (Color::Red, "red"),
+    (Color::Green, "green"),
+    (Color::Yellow, "yellow"),
+    (Color::Blue, "blue"),
+    (Color::Magenta, "magenta"),
+    (Color::Cyan, "cyan"),
+    (Color::Gray, "gray"),
+    (Color::DarkGray, "dark-gray"),
+    (Color::LightRed, "light-red"),
+    (Color::LightGreen, "light-green"),
+    (Color::LightYellow, "light-yellow"),
+    (Color::LightBlue, "light-blue"),
+    (Color::LightMagenta, "light-magenta"),
+    (Color::LightCyan, "light-cyan"),
+    (Color::White, "white"),
+];
+
+fn format_color(color: Color) -> String {
+    match color {
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::Indexed(index) => index.to_string(),
+        color => COLOR_NAMES
+            .iter()
+            .find(|(c, _)| *c == color)
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn parse_color(str: &str) -> Result<Color, String> {
+    if let Some((color, _)) = COLOR_NAMES.iter().find(|(_, name)| *name == str) {
+        return Ok(*color);
+    }
+    if let Ok(index) = str.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+    match str.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 && hex.is_ascii() => {
+            let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16);
+            if let (Ok(r), Ok(g), Ok(b)) = (channel(0), channel(2), channel(4)) {
+                return Ok(Color::Rgb(r, g, b));
+            }
+        }
+        _ => {}
+    }
+    Err(format!(
+        "Unknown color '{}'. Supports names like 'light-green', hex codes like '#ff8800', or color indices 0-255.",
+        str
+    ))
+}
+
+mod color {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use tui::style::Color;
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format_color(*color))
+    }
+
+    /// Colors as strings, or color indices as plain integers
+    #[derive(Deserialize)]
+    #[serde(untagged, expecting = "a color name, hex code or color index")]
+    enum ColorValue {
+        Index(i64),
+        Name(String),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let str = match ColorValue::deserialize(deserializer)? {
+            ColorValue::Index(index) => index.to_string(),
+            ColorValue::Name(name) => name,
+        };
+        super::parse_color(&str).map_err(serde::de::Error::custom)
+    }
+}
+
+mod color_option {
+    use serde::{Deserializer, Serializer};
+    use tui::style::Color;
+
+    pub fn serialize<S: Serializer>(
+        color: &Option<Color>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match color {
+            Some(color) => super::color::serialize(color, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Color>, D::Error> {
+        super::color::deserialize(deserializer).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a theme file to a temporary directory and loads it
+    fn load_file(name: &str, content: &str) -> Result<Theme, String> {
+        let dir = std::env::temp_dir().join(format!("git-igitt-themes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(format!("{}.toml", name)), content).unwrap();
+        Theme::load(name, &dir)
+    }
+
+    #[test]
+    fn built_in_themes_round_trip() {
+        for name in THEMES {
+            let theme = Theme::built_in(name).unwrap();
+            let toml = theme.to_toml().unwrap();
+            let file = load_file(&format!("copy-{}", name), &toml).unwrap();
+            assert_eq!(file.to_toml().unwrap(), toml);
+        }
+    }
+
+    #[test]
+    fn file_overrides_base_theme() {
+        let theme = load_file(
+            "override",
+            "base = \"light\"\n\
+             hash = \"#ff8800\"\n\
+             [search]\n\
+             fg = \"red\"\n\
+             modifiers = [\"underlined\"]\n",
+        )
+        .unwrap();
+        assert_eq!(theme.hash, Color::Rgb(255, 136, 0));
+        // Styles are replaced as a whole
+        assert_eq!(
+            theme.search.style(),
+            Style::default()
+                .fg(Color::Red)
+                .add_modifier(Modifier::UNDERLINED)
+        );
+        let light = Theme::light();
+        assert_eq!(theme.border.style(), light.border.style());
+        assert_eq!(theme.diff_added.style(), light.diff_added.style());
+    }
+
+    #[test]
+    fn file_defaults_to_dark_base() {
+        let theme = load_file("no-base", "hash = 33").unwrap();
+        assert_eq!(theme.hash, Color::Indexed(33));
+        assert_eq!(theme.border.style(), Theme::dark().border.style());
+    }
+
+    #[test]
+    fn invalid_theme_files() {
+        assert!(load_file("bad-base", "base = \"solarized\"").is_err());
+        assert!(load_file("bad-color", "hash = \"purple\"").is_err());
+        assert!(load_file("bad-index", "hash = 256").is_err());
+        assert!(load_file("bad-type", "hash = true").is_err());
+        assert!(load_file("bad-key", "[search]\ncolor = \"red\"").is_err());
+        let dir = std::env::temp_dir();
+        assert!(Theme::load("git-igitt-missing-theme", &dir).is_err());
+    }
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(parse_color("dark-gray"), Ok(Color::DarkGray));
+        assert_eq!(parse_color("208"), Ok(Color::Indexed(208)));
+        assert_eq!(parse_color("#00ff7f"), Ok(Color::Rgb(0, 255, 127)));
+        assert!(parse_color("#00ff7").is_err());
+        assert!(parse_color("256").is_err());
+        for color in ["light-cyan", "#102030", "17"] {
+            assert_eq!(format_color(parse_color(color).unwrap()), color);
+        }
+    }
+}
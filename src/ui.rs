@@ -1,12 +1,18 @@
-use crate::app::{ActiveView, App, DiffMode};
+use crate::app::{revision_names, ActiveView, App, DiffMode, DiffType};
 use crate::dialogs::FileDialog;
+use crate::keymap::{self, Action, Keymap};
+use crate::theme::Theme;
+use crate::util::diff::{split_lines, SplitLine};
+use crate::util::search::find_matches;
 use crate::util::syntax_highlight::as_styled;
-use crate::widgets::branches_view::{BranchList, BranchListItem};
-use crate::widgets::commit_view::CommitView;
+use crate::widgets::branches_view::{BranchItemType, BranchList, BranchListItem};
+use crate::widgets::commit_view::{CommitView, DiffItem};
+use crate::widgets::file_tree::TreeRow;
 use crate::widgets::files_view::{FileList, FileListItem};
 use crate::widgets::graph_view::GraphView;
 use crate::widgets::models_view::ModelListState;
-use lazy_static::lazy_static;
+use std::borrow::Cow;
+use syntect::highlighting::Style as SynStyle;
 use tui::backend::Backend;
 use tui::layout::{Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Modifier, Style};
@@ -15,12 +21,18 @@ use tui::widgets::{
     Block, BorderType, Borders, Clear, List, ListItem as TuiListItem, Paragraph, Wrap,
 };
 use tui::Frame;
+use unicode_width::UnicodeWidthStr;
 
-lazy_static! {
-    pub static ref HINT_STYLE: Style = Style::default().fg(Color::Cyan);
-}
+/// Maximum number of conflicted files listed in the conflicts dialog
+const MAX_CONFLICT_PATHS: usize = 15;
 
-pub fn draw_open_repo<B: Backend>(f: &mut Frame<B>, dialog: &mut FileDialog) {
+pub fn draw_open_repo<B: Backend>(
+    f: &mut Frame<B>,
+    dialog: &mut FileDialog,
+    theme: &Theme,
+    keymap: &Keymap,
+) {
+    let quit = keymap.key_name(Action::Quit);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Length(4), Constraint::Min(0)].as_ref())
@@ -36,7 +48,10 @@ pub fn draw_open_repo<B: Backend>(f: &mut Frame<B>, dialog: &mut FileDialog) {
     let paragraph = Paragraph::new(format!("{}", &dialog.location.display())).block(location_block);
     f.render_widget(paragraph, top_chunks[0]);
 
-    let help = Paragraph::new("  Navigate with Arrows, confirm with Enter, abort with Esc.");
+    let help = Paragraph::new(format!(
+        "  Navigate with Arrows, confirm with Enter, abort with Esc, quit with {}.",
+        quit
+    ));
     f.render_widget(help, top_chunks[1]);
 
     let list_block = Block::default()
@@ -49,7 +64,7 @@ pub fn draw_open_repo<B: Backend>(f: &mut Frame<B>, dialog: &mut FileDialog) {
         .map(|f| {
             if dialog.color {
                 if f.1 {
-                    TuiListItem::new(&f.0[..]).style(Style::default().fg(Color::LightGreen))
+                    TuiListItem::new(&f.0[..]).style(theme.repository.style())
                 } else {
                     TuiListItem::new(&f.0[..])
                 }
@@ -64,19 +79,19 @@ pub fn draw_open_repo<B: Backend>(f: &mut Frame<B>, dialog: &mut FileDialog) {
     let mut list = List::new(items).block(list_block).highlight_symbol("> ");
 
     if dialog.color {
-        list = list.highlight_style(Style::default().add_modifier(Modifier::UNDERLINED));
+        list = list.highlight_style(theme.selection.style());
     }
 
     f.render_stateful_widget(list, chunks[1], &mut dialog.state);
 
     if let Some(error) = &dialog.error_message {
-        draw_error_dialog(f, f.size(), error, dialog.color);
+        draw_error_dialog(f, f.size(), error, &quit, theme, dialog.color);
     }
 }
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     if let ActiveView::Help(scroll) = app.active_view {
-        draw_help(f, f.size(), scroll);
+        draw_help(f, f.size(), scroll, &app.settings.keymap);
         return;
     }
 
@@ -89,19 +104,28 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
         let help = Paragraph::new("  Enter = confirm, P = permanent, Esc = abort.");
         f.render_widget(help, chunks[0]);
 
-        draw_models(f, chunks[1], app.color, model_state);
+        draw_models(f, chunks[1], &app.settings.theme, app.color, model_state);
         return;
     }
 
+    app.layout.panels.clear();
+    app.layout.main = None;
+    app.layout.sub = None;
+
     if app.is_fullscreen {
-        let view = if app.active_view == ActiveView::Search {
-            app.prev_active_view.as_ref().unwrap_or(&ActiveView::Graph)
+        let view = if app.active_view == ActiveView::Search
+            || app.active_view == ActiveView::Filter
+            || app.active_view == ActiveView::Dialog
+        {
+            app.prev_active_view.unwrap_or(ActiveView::Graph)
         } else {
-            &app.active_view
+            app.active_view
         };
+        app.layout.panels.push((view, f.size()));
         match view {
             ActiveView::Branches => draw_branches(f, f.size(), app),
             ActiveView::Graph => draw_graph(f, f.size(), app),
+            ActiveView::Reflog => draw_reflog(f, f.size(), app),
             ActiveView::Commit => draw_commit(f, f.size(), app),
             ActiveView::Files => draw_files(f, f.size(), app),
             ActiveView::Diff => draw_diff(f, f.size(), app),
@@ -132,98 +156,272 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
             )
             .split(f.size());
 
+        let main_percent = app.layout.main_percent;
         let chunks = Layout::default()
-            .direction(base_split)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .direction(base_split.clone())
+            .constraints(
+                [
+                    Constraint::Percentage(main_percent),
+                    Constraint::Percentage(100 - main_percent),
+                ]
+                .as_ref(),
+            )
             .split(top_chunks[1]);
 
+        let sub_percent = app.layout.sub_percent;
         let right_chunks = Layout::default()
-            .direction(sub_split)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .direction(sub_split.clone())
+            .constraints(
+                [
+                    Constraint::Percentage(sub_percent),
+                    Constraint::Percentage(100 - sub_percent),
+                ]
+                .as_ref(),
+            )
             .split(chunks[1]);
 
-        match app.active_view {
-            ActiveView::Search => {
-                if let Some(prev) = &app.prev_active_view {
-                    match prev {
-                        ActiveView::Files | ActiveView::Diff => draw_diff(f, chunks[0], app),
-                        _ => draw_graph(f, chunks[0], app),
-                    }
-                } else {
-                    draw_graph(f, chunks[0], app)
-                }
+        let view = match app.active_view {
+            ActiveView::Search | ActiveView::Filter | ActiveView::Dialog => {
+                app.prev_active_view.unwrap_or(ActiveView::Graph)
+            }
+            view => view,
+        };
+        match view {
+            ActiveView::Files | ActiveView::Diff => {
+                app.layout.panels.push((ActiveView::Diff, chunks[0]));
+                draw_diff(f, chunks[0], app)
+            }
+            _ if app.reflog_state.is_some() => {
+                app.layout.panels.push((ActiveView::Reflog, chunks[0]));
+                draw_reflog(f, chunks[0], app)
+            }
+            _ => {
+                app.layout.panels.push((ActiveView::Graph, chunks[0]));
+                draw_graph(f, chunks[0], app)
             }
-            ActiveView::Files | ActiveView::Diff => draw_diff(f, chunks[0], app),
-            _ => draw_graph(f, chunks[0], app),
         }
 
         if show_branches {
+            app.layout
+                .panels
+                .push((ActiveView::Branches, top_chunks[0]));
             draw_branches(f, top_chunks[0], app);
         }
+        app.layout
+            .panels
+            .push((ActiveView::Commit, right_chunks[0]));
+        app.layout.panels.push((ActiveView::Files, right_chunks[1]));
+        app.layout.main = Some((top_chunks[1], base_split));
+        app.layout.sub = Some((chunks[1], sub_split));
         draw_commit(f, right_chunks[0], app);
         draw_files(f, right_chunks[1], app);
     }
 
     if let Some(error) = &app.error_message {
-        draw_error_dialog(f, f.size(), error, app.color);
+        let quit = app.settings.keymap.key_name(Action::Quit);
+        draw_error_dialog(f, f.size(), error, &quit, &app.settings.theme, app.color);
+    } else if let Some((action, paths)) = &app.conflicts {
+        draw_conflicts_dialog(f, f.size(), action, paths, &app.settings.theme, app.color);
     } else if app.active_view == ActiveView::Search {
-        draw_search_dialog(f, f.size(), &app.search_term);
+        let empty = "".to_string();
+        let text = app.search_term.as_ref().unwrap_or(&empty);
+        draw_input_dialog(
+            f,
+            f.size(),
+            " Search - Search with Enter, abort with Esc ",
+            &format!("{}_", text),
+        );
+    } else if app.active_view == ActiveView::Filter {
+        draw_input_dialog(
+            f,
+            f.size(),
+            " Filter - author:<name> since:<YYYY-MM-DD> until:<YYYY-MM-DD> path:<path> range:<A..B> <message> ",
+            &format!("{}_", app.filter_input),
+        );
+    } else if let Some(dialog) = &app.action_dialog {
+        draw_input_dialog(f, f.size(), &dialog.title(), &dialog.text());
+    }
+}
+
+/// The style of a file in the files panel, by its status
+fn file_style(theme: &Theme, diff_type: &DiffType) -> Style {
+    match diff_type {
+        DiffType::Added => theme.file_added.style(),
+        DiffType::Deleted => theme.file_deleted.style(),
+        DiffType::Modified => theme.file_modified.style(),
+        DiffType::Renamed => theme.file_renamed.style(),
     }
 }
 
-fn create_title<'a>(title: &'a str, hint: &'a str, color: bool) -> Spans<'a> {
-    Spans(vec![
+/// The block of a panel, with a title and a navigation hint
+fn panel_block<'a>(title: &'a str, hint: &'a str, theme: &Theme, color: bool) -> Block<'a> {
+    let title = Spans(vec![
         Span::raw(format!(" {} ", title)),
         if color {
-            Span::styled(hint, *HINT_STYLE)
+            Span::styled(hint, theme.hint.style())
         } else {
             Span::raw(hint)
         },
-    ])
+    ]);
+    let block = Block::default().borders(Borders::ALL).title(title);
+    if color {
+        block.border_style(theme.border.style())
+    } else {
+        block
+    }
+}
+
+/// Marks the block of the active panel by a thick border
+fn set_active<'a>(block: Block<'a>, theme: &Theme, color: bool) -> Block<'a> {
+    let block = block.border_type(BorderType::Thick);
+    if color {
+        block.border_style(theme.active_border.style())
+    } else {
+        block
+    }
 }
 
 fn draw_graph<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
-    let title = format!("Graph - {}", app.repo_name);
-    let mut block = Block::default().borders(Borders::ALL).title(create_title(
+    let title = if app.graph_state.graph.is_some() {
+        if app.filter.is_empty() {
+            format!("Graph - {}", app.repo_name)
+        } else {
+            format!(
+                "Graph - {} (filter: {}, {} of {} commits)",
+                app.repo_name,
+                app.filter,
+                app.graph_state.num_matches(),
+                app.total_commits
+            )
+        }
+    } else {
+        format!("Graph - {}", app.repo_name)
+    };
+    let title = if let Some(loader) = &app.loader {
+        let elapsed = loader.started.elapsed().as_secs_f32();
+        match &app.graph_state.graph {
+            Some(_) if !loader.incremental => format!("{} (reloading, {:.1} s)", title, elapsed),
+            Some(_) => format!(
+                "{} (loading, {} commits so far, {:.1} s)",
+                title, app.total_commits, elapsed
+            ),
+            None => format!("{} (loading, {:.1} s)", title, elapsed),
+        }
+    } else {
+        title
+    };
+    let title = match &app.compare_target {
+        Some((name, _)) => format!("{} (compare to {})", title, name),
+        None => title,
+    };
+    let title = match panel_status(app, ActiveView::Graph) {
+        Some(status) => format!("{} - {}", title, status),
+        None => title,
+    };
+    let mut block = panel_block(
         &title,
         " <-Branches | Commit-> ",
+        &app.settings.theme,
         app.color,
-    ));
+    );
 
     if app.active_view == ActiveView::Graph {
-        block = block.border_type(BorderType::Thick);
+        block = set_active(block, &app.settings.theme, app.color);
     }
 
     let mut graph = GraphView::default().block(block).highlight_symbol(">", "#");
 
     if app.color {
-        graph = graph.highlight_style(Style::default().add_modifier(Modifier::UNDERLINED));
+        graph = graph
+            .highlight_style(app.settings.theme.selection.style())
+            .filtered_style(app.settings.theme.filtered.style());
+    } else {
+        graph = graph.filtered_style(Style::default().add_modifier(Modifier::DIM));
     }
 
     f.render_stateful_widget(graph, target, &mut app.graph_state);
 }
 
+fn draw_reflog<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
+    let color = app.color;
+    let state = match &mut app.reflog_state {
+        Some(state) => state,
+        None => return,
+    };
+    let title = format!("Reflog - {}", state.name);
+    let mut block = panel_block(
+        &title,
+        " <-Branches | Commit-> ",
+        &app.settings.theme,
+        color,
+    );
+    if app.active_view == ActiveView::Reflog {
+        block = set_active(block, &app.settings.theme, app.color);
+    }
+
+    let selector_width = state
+        .entries
+        .items
+        .iter()
+        .map(|item| item.selector.len())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<_> = state
+        .entries
+        .items
+        .iter()
+        .map(|item| {
+            let prefix = format!(
+                "{:<width$} {}..{} {} ",
+                item.selector,
+                &item.old.to_string()[..7],
+                &item.new.to_string()[..7],
+                item.time,
+                width = selector_width
+            );
+            if color {
+                FileListItem::new(
+                    Span::raw(&item.message[..]),
+                    Span::styled(prefix, Style::default().fg(app.settings.theme.hash)),
+                )
+            } else {
+                FileListItem::new(Span::raw(&item.message[..]), Span::raw(prefix))
+            }
+        })
+        .collect();
+
+    let mut list = FileList::new(items).block(block).highlight_symbol("> ");
+
+    if color {
+        list = list.highlight_style(app.settings.theme.selection.style());
+    }
+
+    f.render_stateful_widget(list, target, &mut state.entries.state);
+}
+
 fn draw_branches<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
     let color = app.color;
 
-    let mut block = Block::default().borders(Borders::ALL).title(create_title(
-        "Branches",
-        " Graph-> ",
-        app.color,
-    ));
+    let mut block = panel_block("Branches", " Graph-> ", &app.settings.theme, app.color);
 
     if let Some(state) = &mut app.graph_state.branches {
         if app.active_view == ActiveView::Branches {
-            block = block.border_type(BorderType::Thick);
+            block = set_active(block, &app.settings.theme, app.color);
         }
 
         let items: Vec<_> = state
             .items
             .iter()
             .map(|item| {
+                let theme = &app.settings.theme;
                 BranchListItem::new(
                     if color {
-                        Span::styled(&item.name, Style::default().fg(Color::Indexed(item.color)))
+                        let style = match item.branch_type {
+                            BranchItemType::Heading => theme.branch_heading.style(),
+                            BranchItemType::Stash { .. } => theme.stash.style(),
+                            _ => Style::default().fg(Color::Indexed(item.color)),
+                        };
+                        Span::styled(&item.name, style)
                     } else {
                         Span::raw(&item.name)
                     },
@@ -235,27 +433,28 @@ fn draw_branches<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
         let mut list = BranchList::new(items).block(block).highlight_symbol("> ");
 
         if color {
-            list = list.highlight_style(Style::default().add_modifier(Modifier::UNDERLINED));
+            list = list.highlight_style(app.settings.theme.selection.style());
         }
 
         f.render_stateful_widget(list, target, &mut state.state);
     } else {
         if app.active_view == ActiveView::Files {
-            block = block.border_type(BorderType::Thick);
+            block = set_active(block, &app.settings.theme, app.color);
         }
         f.render_widget(block, target);
     }
 }
 
 fn draw_commit<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
-    let mut block = Block::default().borders(Borders::ALL).title(create_title(
+    let mut block = panel_block(
         "Commit",
         " <-Graph | Files-> ",
+        &app.settings.theme,
         app.color,
-    ));
+    );
 
     if app.active_view == ActiveView::Commit {
-        block = block.border_type(BorderType::Thick);
+        block = set_active(block, &app.settings.theme, app.color);
     }
 
     let commit = CommitView::default().block(block).highlight_symbol(">");
@@ -265,138 +464,202 @@ fn draw_commit<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
 
 fn draw_files<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
     let color = app.color;
+    let status = panel_status(app, ActiveView::Files);
     if let Some(state) = &mut app.commit_state.content {
-        let title = format!(
-            "Files ({}..{})",
-            &state.compare_oid.to_string()[..7],
-            &state.oid.to_string()[..7]
-        );
-        let mut block = Block::default().borders(Borders::ALL).title(create_title(
+        let (old, new) = revision_names(state.oid, state.compare_oid, state.pseudo);
+        let tree = app.diff_options.file_tree;
+        let title = if tree {
+            format!("Files ({}..{}) - tree", old, new)
+        } else {
+            format!("Files ({}..{})", old, new)
+        };
+        let title = match status {
+            Some(status) => format!("{} - {}", title, status),
+            None => title,
+        };
+        let mut block = panel_block(
             &title,
             " <-Commit | Diff-> ",
+            &app.settings.theme,
             app.color,
-        ));
+        );
 
         if app.active_view == ActiveView::Files {
-            block = block.border_type(BorderType::Thick);
+            block = set_active(block, &app.settings.theme, app.color);
         }
 
-        let items: Vec<_> = state
-            .diffs
-            .items
-            .iter()
-            .map(|item| {
-                if color {
-                    let style = Style::default().fg(item.diff_type.to_color());
-                    FileListItem::new(
-                        Span::styled(&item.file, style),
-                        Span::styled(format!("{} ", item.diff_type), style),
-                    )
-                } else {
-                    FileListItem::new(
-                        Span::raw(&item.file),
-                        Span::raw(format!("{} ", item.diff_type)),
-                    )
-                }
-            })
-            .collect();
+        let items: Vec<_> = if tree {
+            state.sync_tree();
+            state
+                .tree
+                .rows
+                .iter()
+                .map(|row| tree_item(row, &state.diffs.items, &app.settings.theme, color))
+                .collect()
+        } else {
+            state
+                .diffs
+                .items
+                .iter()
+                .map(|item| {
+                    if color {
+                        let style = file_style(&app.settings.theme, &item.diff_type);
+                        FileListItem::new(
+                            Span::styled(&item.file, style),
+                            Span::styled(format!("{} ", item.diff_type), style),
+                        )
+                    } else {
+                        FileListItem::new(
+                            Span::raw(&item.file),
+                            Span::raw(format!("{} ", item.diff_type)),
+                        )
+                    }
+                })
+                .collect()
+        };
 
         let mut list = FileList::new(items).block(block).highlight_symbol("> ");
 
         if color {
-            list = list.highlight_style(Style::default().add_modifier(Modifier::UNDERLINED));
+            list = list.highlight_style(app.settings.theme.selection.style());
         }
 
-        f.render_stateful_widget(list, target, &mut state.diffs.state);
+        let list_state = if tree {
+            &mut state.tree.state
+        } else {
+            &mut state.diffs.state
+        };
+        f.render_stateful_widget(list, target, list_state);
     } else {
-        let mut block = Block::default().borders(Borders::ALL).title(create_title(
+        let mut block = panel_block(
             "Files",
             " <-Commit | Diff-> ",
+            &app.settings.theme,
             app.color,
-        ));
+        );
         if app.active_view == ActiveView::Files {
-            block = block.border_type(BorderType::Thick);
+            block = set_active(block, &app.settings.theme, app.color);
         }
         f.render_widget(block, target);
     }
 }
 
+/// A directory with its number of changed files, or a file name with its status, indented by depth
+fn tree_item(
+    row: &TreeRow,
+    files: &[DiffItem],
+    theme: &Theme,
+    color: bool,
+) -> FileListItem<'static> {
+    let (prefix, content, style) = match row {
+        TreeRow::Dir {
+            name,
+            depth,
+            files: count,
+            collapsed,
+            ..
+        } => (
+            format!(
+                "{}{} ",
+                "  ".repeat(*depth),
+                if *collapsed { '▸' } else { '▾' }
+            ),
+            format!("{}/ ({})", name, count),
+            theme.directory.style(),
+        ),
+        TreeRow::File { index, depth } => {
+            let item = &files[*index];
+            let name = item.file.rsplit('/').next().unwrap_or(&item.file);
+            (
+                format!("{}{} ", "  ".repeat(*depth), item.diff_type),
+                name.to_string(),
+                file_style(theme, &item.diff_type),
+            )
+        }
+    };
+    if color {
+        FileListItem::new(Span::styled(content, style), Span::styled(prefix, style))
+    } else {
+        FileListItem::new(Span::raw(content), Span::raw(prefix))
+    }
+}
+
 fn draw_diff<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
+    if let Some(state) = &mut app.diff_state.content {
+        if let Some(blame) = &state.blame {
+            // Keep the blame cursor visible
+            let height = target.height.saturating_sub(2).max(1);
+            let line = (blame.header_lines + blame.cursor) as u16;
+            if line < state.scroll.0 {
+                state.scroll.0 = line;
+            } else if line >= state.scroll.0 + height {
+                state.scroll.0 = line + 1 - height;
+            }
+        }
+    }
     if let Some(state) = &app.diff_state.content {
+        let (old, new) = revision_names(state.oid, state.compare_oid, state.pseudo);
         let title = match app.diff_options.diff_mode {
-            DiffMode::Diff => format!(
-                "Diff ({}..{})",
-                &state.compare_oid.to_string()[..7],
-                &state.oid.to_string()[..7]
-            ),
-            DiffMode::Old => format!("Diff (old: {})", &state.compare_oid.to_string()[..7],),
-            DiffMode::New => format!("Diff (new: {})", &state.oid.to_string()[..7],),
+            DiffMode::Diff => format!("Diff ({}..{})", old, new),
+            DiffMode::Split => format!("Split diff ({}..{})", old, new),
+            DiffMode::Old => format!("Diff (old: {})", old),
+            DiffMode::New => format!("Diff (new: {})", new),
         };
-        let mut block = Block::default().borders(Borders::ALL).title(create_title(
-            &title,
-            " <-Files ",
-            app.color,
-        ));
+        let title = if state.blame.is_some() {
+            format!("{} - blame", title)
+        } else {
+            title
+        };
+        let title = match panel_status(app, ActiveView::Diff) {
+            Some(status) => format!("{} - {}", title, status),
+            None => title,
+        };
+        let mut block = panel_block(&title, " <-Files ", &app.settings.theme, app.color);
         if app.active_view == ActiveView::Diff {
-            block = block.border_type(BorderType::Thick);
+            block = set_active(block, &app.settings.theme, app.color);
         }
 
+        let theme = &app.settings.theme;
         let styles = [
-            Style::default().fg(Color::LightGreen),
-            Style::default().fg(Color::LightRed),
-            Style::default().fg(Color::LightBlue),
+            theme.diff_added.style(),
+            theme.diff_removed.style(),
+            theme.diff_hunk_header.style(),
             Style::default(),
+            theme.diff_added_word.style(),
+            theme.diff_removed_word.style(),
         ];
+        let no_changes = vec![];
+        let changes = |idx: usize| {
+            if app.diff_options.word_diff {
+                &state.changes[idx]
+            } else {
+                &no_changes
+            }
+        };
+        let style_line = |prefix: Option<String>, line, idx: usize| match state
+            .highlighted
+            .as_ref()
+            .and_then(|lines| lines.get(idx))
+        {
+            Some(tokens) if !tokens.is_empty() => {
+                style_highlighted_line(prefix, line, tokens, changes(idx), &styles, theme)
+            }
+            _ => style_diff_line(prefix, line, changes(idx), &styles, app.color),
+        };
 
         let mut text = Text::from("");
+        let mut text_new = Text::from("");
         if app.diff_options.diff_mode == DiffMode::Diff {
-            let (space_old_ln, space_new_ln, empty_old_ln, empty_new_ln) =
-                if app.diff_options.line_numbers {
-                    let mut max_old_ln = None;
-                    let mut max_new_ln = None;
-
-                    for (_, old_ln, new_ln) in state.diffs.iter().rev() {
-                        if max_old_ln.is_none() {
-                            if let Some(old_ln) = old_ln {
-                                max_old_ln = Some(*old_ln);
-                            }
-                        }
-                        if max_new_ln.is_none() {
-                            if let Some(new_ln) = new_ln {
-                                max_new_ln = Some(*new_ln);
-                            }
-                        }
-                        if max_old_ln.is_some() && max_new_ln.is_some() {
-                            break;
-                        }
-                    }
-
-                    let space_old_ln =
-                        std::cmp::max(3, (max_old_ln.unwrap_or(0) as f32).log10().ceil() as usize);
-                    let space_new_ln =
-                        std::cmp::max(3, (max_new_ln.unwrap_or(0) as f32).log10().ceil() as usize)
-                            + 1;
-
-                    (
-                        space_old_ln,
-                        space_new_ln,
-                        " ".repeat(space_old_ln),
-                        " ".repeat(space_new_ln),
-                    )
-                } else {
-                    (0, 0, String::new(), String::new())
-                };
+            let (space_old_ln, space_new_ln) = if app.diff_options.line_numbers {
+                let (space_old_ln, space_new_ln) = line_number_widths(&state.diffs);
+                (space_old_ln, space_new_ln + 1)
+            } else {
+                (0, 0)
+            };
+            let (empty_old_ln, empty_new_ln) = (" ".repeat(space_old_ln), " ".repeat(space_new_ln));
 
-            for (line, old_ln, new_ln) in &state.diffs {
-                let ln = if line.starts_with("@@ ") {
-                    if let Some(pos) = line.find(" @@ ") {
-                        &line[..pos + 3]
-                    } else {
-                        line
-                    }
-                } else {
-                    line
-                };
+            for (idx, (line, old_ln, new_ln)) in state.diffs.iter().enumerate() {
+                let ln = trim_hunk_header(line);
 
                 if app.diff_options.line_numbers && (old_ln.is_some() || new_ln.is_some()) {
                     let l1 = old_ln
@@ -407,14 +670,55 @@ fn draw_diff<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
                         .unwrap_or_else(|| empty_new_ln.clone());
                     let fmt = format!("{}{}|", l1, l2);
 
-                    text.extend(style_diff_line(Some(fmt), ln, &styles, app.color));
+                    text.extend(style_line(Some(fmt), ln, idx));
                 } else {
-                    text.extend(style_diff_line(None, ln, &styles, app.color));
+                    text.extend(style_line(None, ln, idx));
+                }
+            }
+        } else if app.diff_options.diff_mode == DiffMode::Split {
+            let (space_old_ln, space_new_ln) = if app.diff_options.line_numbers {
+                line_number_widths(&state.diffs)
+            } else {
+                (0, 0)
+            };
+
+            for row in split_lines(&state.diffs) {
+                match row {
+                    SplitLine::Header(idx) => {
+                        let ln = trim_hunk_header(&state.diffs[idx].0);
+                        text.extend(style_diff_line(None, ln, &[], &styles, app.color));
+                        text_new.extend(style_diff_line(None, ln, &[], &styles, app.color));
+                    }
+                    SplitLine::Lines(old, new) => {
+                        for (side, idx, width, text) in [
+                            (0, old, space_old_ln, &mut text),
+                            (1, new, space_new_ln, &mut text_new),
+                        ] {
+                            if let Some(idx) = idx {
+                                let (line, old_ln, new_ln) = &state.diffs[idx];
+                                let prefix = if app.diff_options.line_numbers {
+                                    let ln = if side == 0 { old_ln } else { new_ln };
+                                    ln.map(|v| format!("{:>width$}|", v, width = width))
+                                } else {
+                                    None
+                                };
+                                text.extend(style_line(prefix, line, idx));
+                            } else {
+                                text.lines.push(Spans::default());
+                            }
+                        }
+                    }
                 }
             }
         } else {
             if !state.diffs.is_empty() {
-                text.extend(style_diff_line(None, &state.diffs[0].0, &styles, false));
+                text.extend(style_diff_line(
+                    None,
+                    &state.diffs[0].0,
+                    &[],
+                    &styles,
+                    false,
+                ));
             }
             if !state.diffs.len() > 1 {
                 if let Some(txt) = &state.highlighted {
@@ -428,39 +732,200 @@ fn draw_diff<B: Backend>(f: &mut Frame<B>, target: Rect, app: &mut App) {
                             if trim.is_empty() {
                                 text.extend(Text::raw("\n"));
                             } else {
-                                let styled = style_diff_line(None, trim, &styles, false);
+                                let styled = style_diff_line(None, trim, &[], &styles, false);
                                 text.extend(styled);
                             }
                         }
                     }
                 }
             }
+
+            if let Some(blame) = &state.blame {
+                let lines = text.lines.iter_mut().skip(blame.header_lines);
+                for (idx, (line, (_, annotation))) in lines.zip(blame.lines.iter()).enumerate() {
+                    let mut style = if app.color {
+                        Style::default().fg(theme.hash)
+                    } else {
+                        Style::default()
+                    };
+                    if idx == blame.cursor {
+                        style = style.add_modifier(Modifier::REVERSED);
+                    }
+                    line.0
+                        .insert(0, Span::styled(format!("{} ", annotation), style));
+                }
+            }
         }
 
-        let mut paragraph = Paragraph::new(text).block(block).scroll(state.scroll);
+        if let Some(term) = &app.search_term {
+            let style = if app.color {
+                theme.search.style()
+            } else {
+                Style::default().add_modifier(Modifier::REVERSED)
+            };
+            highlight_matches(&mut text, term, style);
+            highlight_matches(&mut text_new, term, style);
+        }
 
-        if app.diff_options.wrap_lines {
-            paragraph = paragraph.wrap(Wrap { trim: false });
+        let wrap = app.diff_options.wrap_lines && app.diff_options.diff_mode != DiffMode::Split;
+        let mut scroll = state.scroll;
+        if state.scroll_to_search {
+            if let Some(line) = state.search_line {
+                scroll.0 = if wrap {
+                    let width = block.inner(target).width;
+                    text.lines
+                        .iter()
+                        .take(line)
+                        .map(|spans| wrapped_rows(spans, width))
+                        .sum::<usize>() as u16
+                } else {
+                    line as u16
+                };
+            }
         }
 
-        f.render_widget(paragraph, target);
+        if app.diff_options.diff_mode == DiffMode::Split {
+            let inner = block.inner(target);
+            f.render_widget(block, target);
+
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(inner);
+
+            f.render_widget(Paragraph::new(text).scroll(scroll), columns[0]);
+            f.render_widget(
+                Paragraph::new(text_new)
+                    .block(Block::default().borders(Borders::LEFT))
+                    .scroll(scroll),
+                columns[1],
+            );
+        } else {
+            let mut paragraph = Paragraph::new(text).block(block).scroll(scroll);
+
+            if wrap {
+                paragraph = paragraph.wrap(Wrap { trim: false });
+            }
+
+            f.render_widget(paragraph, target);
+        }
+        if let Some(state) = &mut app.diff_state.content {
+            state.scroll = scroll;
+            state.scroll_to_search = false;
+        }
     } else {
-        let mut block = Block::default().borders(Borders::ALL).title(create_title(
-            "Diff",
-            " <-Files ",
-            app.color,
-        ));
+        let mut block = panel_block("Diff", " <-Files ", &app.settings.theme, app.color);
         if app.active_view == ActiveView::Diff {
-            block = block.border_type(BorderType::Thick);
+            block = set_active(block, &app.settings.theme, app.color);
         }
         f.render_widget(block, target);
     }
 }
 
+/// The status message, if it is shown in the title of the given panel.
+/// It is shown in the files or diff panel while active, e.g. for searches, otherwise in the graph.
+fn panel_status(app: &App, view: ActiveView) -> Option<String> {
+    let active = match app.active_view {
+        ActiveView::Search | ActiveView::Filter | ActiveView::Dialog => {
+            app.prev_active_view.unwrap_or(ActiveView::Graph)
+        }
+        active => active,
+    };
+    let status_view = match active {
+        ActiveView::Files | ActiveView::Diff => active,
+        _ => ActiveView::Graph,
+    };
+    app.status()
+        .filter(|_| view == status_view)
+        .map(|status| status.to_string())
+}
+
+/// Returns the number of digits required for old and new line numbers
+fn line_number_widths(diffs: &[(String, Option<u32>, Option<u32>)]) -> (usize, usize) {
+    let mut max_old_ln = None;
+    let mut max_new_ln = None;
+
+    for (_, old_ln, new_ln) in diffs.iter().rev() {
+        if max_old_ln.is_none() {
+            if let Some(old_ln) = old_ln {
+                max_old_ln = Some(*old_ln);
+            }
+        }
+        if max_new_ln.is_none() {
+            if let Some(new_ln) = new_ln {
+                max_new_ln = Some(*new_ln);
+            }
+        }
+        if max_old_ln.is_some() && max_new_ln.is_some() {
+            break;
+        }
+    }
+
+    (
+        std::cmp::max(3, (max_old_ln.unwrap_or(0) as f32).log10().ceil() as usize),
+        std::cmp::max(3, (max_new_ln.unwrap_or(0) as f32).log10().ceil() as usize),
+    )
+}
+
+/// Number of rows a line takes in a `Paragraph` of the given width with `Wrap { trim: false }`.
+/// Follows the word wrapping of tui, which is not public.
+fn wrapped_rows(line: &Spans, width: u16) -> usize {
+    let width = width as usize;
+    let mut rows = 1;
+    // Width and whether it is whitespace, of each grapheme in the current row
+    let mut row: Vec<(usize, bool)> = vec![];
+    let mut word_end = 0;
+    let mut prev_whitespace = false;
+    for span in &line.0 {
+        for grapheme in span.styled_graphemes(Style::default()) {
+            let symbol = grapheme.symbol;
+            let symbol_width = symbol.width();
+            if symbol_width > width {
+                continue;
+            }
+            let whitespace = symbol.chars().all(char::is_whitespace) && symbol != "\u{00a0}";
+            if whitespace && !prev_whitespace {
+                word_end = row.len();
+            }
+            row.push((symbol_width, whitespace));
+            if row.iter().map(|(w, _)| w).sum::<usize>() > width {
+                let wrap_at = if word_end != 0 {
+                    word_end
+                } else {
+                    row.len() - 1
+                };
+                let remainder = &row[wrap_at..];
+                let start = remainder
+                    .iter()
+                    .position(|(_, ws)| !ws)
+                    .unwrap_or(remainder.len());
+                row = remainder[start..].to_vec();
+                rows += 1;
+                word_end = 0;
+                prev_whitespace = false;
+                continue;
+            }
+            prev_whitespace = whitespace;
+        }
+    }
+    rows
+}
+
+/// Strips the function context from hunk headers
+fn trim_hunk_header(line: &str) -> &str {
+    if line.starts_with("@@ ") {
+        if let Some(pos) = line.find(" @@ ") {
+            return &line[..pos + 3];
+        }
+    }
+    line
+}
+
 fn style_diff_line<'a>(
     prefix: Option<String>,
     line: &'a str,
-    styles: &'a [Style; 4],
+    changes: &[(usize, usize)],
+    styles: &'a [Style; 6],
     color: bool,
 ) -> Text<'a> {
     if !color {
@@ -470,16 +935,34 @@ fn style_diff_line<'a>(
             Text::raw(line)
         }
     } else {
-        let style = if line.starts_with('+') {
-            styles[0]
+        let (style, changed_style) = if line.starts_with('+') {
+            (styles[0], styles[4])
         } else if line.starts_with('-') {
-            styles[1]
+            (styles[1], styles[5])
         } else if line.starts_with('@') {
-            styles[2]
+            (styles[2], styles[2])
         } else {
-            styles[3]
+            (styles[3], styles[3])
         };
-        if let Some(prefix) = prefix {
+        if !changes.is_empty() {
+            let line = line.trim_end_matches(['\n', '\r']);
+            let mut spans = vec![];
+            if let Some(prefix) = prefix {
+                spans.push(Span::styled(prefix, style));
+            }
+            let mut pos = 0;
+            for (start, end) in changes {
+                if *start > pos {
+                    spans.push(Span::styled(&line[pos..*start], style));
+                }
+                spans.push(Span::styled(&line[*start..*end], changed_style));
+                pos = *end;
+            }
+            if pos < line.len() {
+                spans.push(Span::styled(&line[pos..], style));
+            }
+            Text::from(Spans::from(spans))
+        } else if let Some(prefix) = prefix {
             Text::styled(format!("{}{}", prefix, line), style)
         } else {
             Text::styled(line, style)
@@ -487,9 +970,125 @@ fn style_diff_line<'a>(
     }
 }
 
+/// Styles a syntax-highlighted line of a diff, with the background of added or removed lines.
+/// `tokens` must match the line's content after the leading `+`, `-` or space.
+fn style_highlighted_line<'a>(
+    prefix: Option<String>,
+    line: &'a str,
+    tokens: &[(SynStyle, String)],
+    changes: &[(usize, usize)],
+    styles: &[Style; 6],
+    theme: &Theme,
+) -> Text<'a> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let (style, background, changed_background) = if line.starts_with('+') {
+        (
+            styles[0],
+            Some(theme.syntax_added_bg),
+            theme.syntax_added_word_bg,
+        )
+    } else if line.starts_with('-') {
+        (
+            styles[1],
+            Some(theme.syntax_removed_bg),
+            theme.syntax_removed_word_bg,
+        )
+    } else {
+        (styles[3], None, theme.syntax_added_word_bg)
+    };
+    let with_background = |style: Style| match background {
+        Some(color) => style.bg(color),
+        None => style,
+    };
+
+    let mut spans = vec![];
+    if let Some(prefix) = prefix {
+        spans.push(Span::styled(prefix, style));
+    }
+    spans.push(Span::styled(&line[..1], with_background(style)));
+    let mut pos = 1;
+    for (token_style, token) in tokens {
+        let end = (pos + token.len()).min(line.len());
+        let fg = Style::default().fg(Color::Rgb(
+            token_style.foreground.r,
+            token_style.foreground.g,
+            token_style.foreground.b,
+        ));
+        // Split the token where word-level changes start or end
+        while pos < end {
+            let (split, changed) = match changes.iter().find(|(_, change_end)| *change_end > pos) {
+                Some((start, change_end)) if *start <= pos => (*change_end, true),
+                Some((start, _)) => (*start, false),
+                None => (end, false),
+            };
+            let split = split.min(end);
+            let style = if changed {
+                fg.bg(changed_background)
+            } else {
+                with_background(fg)
+            };
+            spans.push(Span::styled(&line[pos..split], style));
+            pos = split;
+        }
+    }
+    Text::from(Spans::from(spans))
+}
+
+/// Highlights all (case-insensitive) occurrences of `term` in `text`
+fn highlight_matches(text: &mut Text, term: &str, style: Style) {
+    if term.is_empty() {
+        return;
+    }
+    for spans in text.lines.iter_mut() {
+        let line: String = spans.0.iter().map(|span| &span.content[..]).collect();
+        let matches = find_matches(&line, term);
+        if matches.is_empty() {
+            continue;
+        }
+
+        let mut new_spans = vec![];
+        let mut span_start = 0;
+        for span in spans.0.drain(..) {
+            let span_end = span_start + span.content.len();
+            let mut pos = span_start;
+            for (m_start, m_end) in matches
+                .iter()
+                .filter(|(m_start, m_end)| *m_end > span_start && *m_start < span_end)
+            {
+                let start = (*m_start).max(span_start);
+                let end = (*m_end).min(span_end);
+                if start > pos {
+                    new_spans.push(sub_span(&span, pos - span_start, start - span_start));
+                }
+                let mut matched = sub_span(&span, start - span_start, end - span_start);
+                matched.style = matched.style.patch(style);
+                new_spans.push(matched);
+                pos = end;
+            }
+            if pos < span_end {
+                new_spans.push(sub_span(&span, pos - span_start, span_end - span_start));
+            }
+            span_start = span_end;
+        }
+        spans.0 = new_spans;
+    }
+}
+
+fn sub_span<'a>(span: &Span<'a>, start: usize, end: usize) -> Span<'a> {
+    let content = match &span.content {
+        Cow::Borrowed(content) => Cow::Borrowed(&content[start..end]),
+        Cow::Owned(content) => Cow::Owned(content[start..end].to_string()),
+    };
+    Span {
+        content,
+        style: span.style,
+    }
+}
+
 fn draw_models<B: Backend>(
     f: &mut Frame<B>,
     target: Rect,
+    theme: &Theme,
     color: bool,
     state: &mut ModelListState,
 ) {
@@ -506,71 +1105,60 @@ fn draw_models<B: Backend>(
     let mut list = List::new(items).block(block).highlight_symbol("> ");
 
     if color {
-        list = list.highlight_style(Style::default().add_modifier(Modifier::UNDERLINED));
+        list = list.highlight_style(theme.selection.style());
     }
 
     f.render_stateful_widget(list, target, &mut state.state);
 }
 
-fn draw_help<B: Backend>(f: &mut Frame<B>, target: Rect, scroll: u16) {
+fn draw_help<B: Backend>(f: &mut Frame<B>, target: Rect, scroll: u16, keymap: &Keymap) {
+    let back = keymap.key_name(Action::DefaultView);
     let block = Block::default()
         .borders(Borders::ALL)
-        .title(" Help [back with Esc] ");
-
-    let paragraph = Paragraph::new(
-        "\n\
-         General\n  \
-         \n  \
-           F1/H               Show this help\n  \
-           Q                  Quit\n  \
-           Ctrl + O           Open repository\n  \
-           M                  Set branching model\n  \
-         \n\
-         Layout/panels\n  \
-         \n  \
-           Left/Right         Change panel\n  \
-           Tab                Panel to fullscreen\n  \
-           Esc                Return to default view\n  \
-           L                  Toggle horizontal/vertical layout\n  \
-           B                  Toggle show branch list\n  \
-         \n\
-         Navigate/select\n  \
-         \n  \
-           Up/Down            Select / navigate / scroll\n  \
-           Shift + Up/Down    Navigate fast\n  \
-           Home/End           Navigate to HEAD/last\n  \
-           Ctrl + Up/Down     Secondary selection (compare arbitrary commits)\n  \
-           Backspace          Clear secondary selection\n  \
-           Ctrl + Left/Right  Scroll horizontal\n  \
-           Enter              Jump to selected branch/tag\n  \
-         \n\
-         Search\n  \
-         \n  \
-           F3/Ctrl+F          Open search dialog\n  \
-           F3                 Continue search\n  \
-         \n\
-         Diffs panel\n  \
-         \n  \
-           +/-                Increase/decrease number of diff context lines\n  \
-           D/N/O              Show diff or new/old version of file\n  \
-           Ctrl + L           Toggle line numbers\n  \
-           Ctrl + W           Toggle line wrapping\n  \
-           S                  Toggle syntax highlighting (new/old file only, turn off if too slow)",
-    )
-    .block(block)
-    .scroll((scroll, 0));
+        .title(format!(" Help [back with {}] ", back));
+
+    let mut text = String::new();
+    for (section, actions) in keymap::SECTIONS {
+        text.push_str(&format!("\n{}\n\n", section));
+        for action in actions.iter() {
+            let keys = keymap
+                .keys(*action)
+                .iter()
+                .map(|key| key.help_name())
+                .collect::<Vec<_>>()
+                .join("/");
+            let keys = if keys.is_empty() {
+                "-".to_string()
+            } else {
+                keys
+            };
+            text.push_str(&format!("  {:<22} {}\n", keys, action.description()));
+        }
+    }
+
+    let paragraph = Paragraph::new(text).block(block).scroll((scroll, 0));
 
     f.render_widget(paragraph, target);
 }
 
-fn draw_error_dialog<B: Backend>(f: &mut Frame<B>, target: Rect, error: &str, color: bool) {
+fn draw_error_dialog<B: Backend>(
+    f: &mut Frame<B>,
+    target: Rect,
+    error: &str,
+    quit: &str,
+    theme: &Theme,
+    color: bool,
+) {
     let mut block = Block::default()
-        .title(" Error - Press Enter to continue ")
+        .title(format!(
+            " Error - Press Enter to continue, {} to quit ",
+            quit
+        ))
         .borders(Borders::ALL)
         .border_type(BorderType::Thick);
 
     if color {
-        block = block.border_style(Style::default().fg(Color::LightRed));
+        block = block.border_style(theme.error.style());
     }
 
     let paragraph = Paragraph::new(error).block(block).wrap(Wrap { trim: true });
@@ -580,17 +1168,58 @@ fn draw_error_dialog<B: Backend>(f: &mut Frame<B>, target: Rect, error: &str, co
     f.render_widget(paragraph, area);
 }
 
-fn draw_search_dialog<B: Backend>(f: &mut Frame<B>, target: Rect, search: &Option<String>) {
+fn draw_conflicts_dialog<B: Backend>(
+    f: &mut Frame<B>,
+    target: Rect,
+    action: &str,
+    paths: &[String],
+    theme: &Theme,
+    color: bool,
+) {
+    let mut block = Block::default()
+        .title(format!(
+            " {} stopped with conflicts - Press Enter to continue ",
+            action
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Thick);
+
+    if color {
+        block = block.border_style(theme.warning.style());
+    }
+
+    let mut text = vec![Spans::from(
+        "Resolve the conflicts in these files, then commit:",
+    )];
+    text.push(Spans::default());
+    for path in paths.iter().take(MAX_CONFLICT_PATHS) {
+        text.push(if color {
+            Spans::from(Span::styled(format!("  {}", path), theme.conflict.style()))
+        } else {
+            Spans::from(format!("  {}", path))
+        });
+    }
+    if paths.len() > MAX_CONFLICT_PATHS {
+        text.push(Spans::from(format!(
+            "  ... and {} more",
+            paths.len() - MAX_CONFLICT_PATHS
+        )));
+    }
+    let height = text.len() as u16 + 2;
+    let paragraph = Paragraph::new(text).block(block);
+
+    let area = centered_rect(70, height, target);
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_input_dialog<B: Backend>(f: &mut Frame<B>, target: Rect, title: &str, text: &str) {
     let block = Block::default()
-        .title(" Search - Search with Enter, abort with Esc ")
+        .title(title)
         .borders(Borders::ALL)
         .border_type(BorderType::Thick);
 
-    let empty = "".to_string();
-    let text = &search.as_ref().unwrap_or(&empty)[..];
-    let paragraph = Paragraph::new(format!("{}_", text))
-        .block(block)
-        .wrap(Wrap { trim: true });
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
 
     let area = centered_rect(60, 12, target);
     f.render_widget(Clear, area);
@@ -627,3 +1256,46 @@ fn centered_rect(size_x: u16, size_y: u16, r: Rect) -> Rect {
         )
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tui::buffer::Buffer;
+    use tui::widgets::Widget;
+
+    /// Row of a marker line rendered below the line, as wrapped by tui
+    fn rendered_rows(line: &str, width: u16) -> usize {
+        let area = Rect::new(0, 0, width, 50);
+        let mut buffer = Buffer::empty(area);
+        let text = Text::from(vec![Spans::from(line), Spans::from("#")]);
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .render(area, &mut buffer);
+        (0..area.height)
+            .find(|y| buffer.get(0, *y).symbol == "#")
+            .unwrap() as usize
+    }
+
+    #[test]
+    fn wrapped_rows_like_tui() {
+        let lines = [
+            "",
+            "short",
+            "fn main() { println!(\"hello world\"); }",
+            "   indented words   with  several   spaces   ",
+            "averyveryverylongwordwithoutanybreaksthatmustbesplit",
+            "+    let x = some_function(argument_one, argument_two);",
+        ];
+        for line in lines {
+            for width in [5, 8, 13, 20, 80] {
+                assert_eq!(
+                    wrapped_rows(&Spans::from(line), width),
+                    rendered_rows(line, width),
+                    "{:?} at width {}",
+                    line,
+                    width
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const BASE64_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Copies text to the clipboard.
+///
+/// Uses the given external command if not empty, which receives the text on stdin.
+/// Otherwise, asks the terminal to copy using an OSC 52 escape sequence,
+/// which also works over SSH, but is not supported by all terminals.
+pub fn copy_to_clipboard(text: &str, command: &str) -> Result<(), String> {
+    if command.trim().is_empty() {
+        copy_osc52(text)
+    } else {
+        copy_command(text, command)
+    }
+}
+
+fn copy_osc52(text: &str) -> Result<(), String> {
+    let mut out = std::io::stdout();
+    write!(out, "\x1B]52;c;{}\x07", base64(text.as_bytes()))
+        .and_then(|_| out.flush())
+        .map_err(|err| err.to_string())
+}
+
+fn copy_command(text: &str, command: &str) -> Result<(), String> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().unwrap_or("");
+    let error = |err: &dyn std::fmt::Display| {
+        format!(
+            "Unable to copy with clipboard command '{}'\n{}",
+            command, err
+        )
+    };
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| error(&err))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|err| error(&err))?;
+    }
+    let status = child.wait().map_err(|err| error(&err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(error(&status))
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let triple = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_CHARS[(triple >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_test_vectors() {
+        // From RFC 4648
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (text, encoded) in vectors {
+            assert_eq!(base64(text.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&[0xfb, 0xff, 0xbf]), "+/+/");
+        assert_eq!(base64("ä".as_bytes()), "w6Q=");
+    }
+
+    #[test]
+    fn copy_with_command() {
+        assert!(copy_to_clipboard("text", "git-igitt-no-such-command").is_err());
+        if cfg!(unix) {
+            assert!(copy_to_clipboard("text", "cat").is_ok());
+            assert!(copy_to_clipboard("text", "false").is_err());
+        }
+    }
+}
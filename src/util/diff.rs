@@ -0,0 +1,257 @@
+use crate::app::DiffLines;
+
+/// Byte ranges of changed parts of a line
+pub type ChangedRanges = Vec<(usize, usize)>;
+
+/// A row of a side-by-side diff, referencing entries of the unified diff lines.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SplitLine {
+    /// File or hunk header, shown on both sides
+    Header(usize),
+    /// Old and new line. `None` for padding where a hunk adds or removes lines
+    Lines(Option<usize>, Option<usize>),
+}
+
+/// Aligns the lines of a unified diff into old and new columns.
+pub fn split_lines(diffs: &DiffLines) -> Vec<SplitLine> {
+    let mut rows = vec![];
+    let mut removed = vec![];
+    let mut added = vec![];
+
+    for (idx, (_, old_ln, new_ln)) in diffs.iter().enumerate() {
+        match (old_ln, new_ln) {
+            (Some(_), None) => removed.push(idx),
+            (None, Some(_)) => added.push(idx),
+            _ => {
+                flush_changes(&mut rows, &mut removed, &mut added);
+                if old_ln.is_some() {
+                    rows.push(SplitLine::Lines(Some(idx), Some(idx)));
+                } else {
+                    rows.push(SplitLine::Header(idx));
+                }
+            }
+        }
+    }
+    flush_changes(&mut rows, &mut removed, &mut added);
+
+    rows
+}
+
+fn flush_changes(rows: &mut Vec<SplitLine>, removed: &mut Vec<usize>, added: &mut Vec<usize>) {
+    for i in 0..removed.len().max(added.len()) {
+        rows.push(SplitLine::Lines(
+            removed.get(i).cloned(),
+            added.get(i).cloned(),
+        ));
+    }
+    removed.clear();
+    added.clear();
+}
+
+/// Pairs removed and added lines within each hunk and determines the changed byte ranges
+/// of each line (including the leading `+`/`-`). Lines without a counterpart have no ranges.
+pub fn word_changes(diffs: &DiffLines) -> Vec<ChangedRanges> {
+    let mut changes = vec![vec![]; diffs.len()];
+    for row in split_lines(diffs) {
+        if let SplitLine::Lines(Some(old), Some(new)) = row {
+            if old != new {
+                let (old_changes, new_changes) = line_changes(&diffs[old].0, &diffs[new].0);
+                changes[old] = old_changes;
+                changes[new] = new_changes;
+            }
+        }
+    }
+    changes
+}
+
+/// Maximum number of token comparisons for a single pair of lines
+const MAX_COMPARISONS: usize = 250_000;
+
+fn line_changes(old: &str, new: &str) -> (ChangedRanges, ChangedRanges) {
+    let old_line = old.get(1..).unwrap_or("").trim_end();
+    let new_line = new.get(1..).unwrap_or("").trim_end();
+    let old_tokens = tokenize(old_line);
+    let new_tokens = tokenize(new_line);
+
+    let (n, m) = (old_tokens.len(), new_tokens.len());
+    if n * m > MAX_COMPARISONS {
+        return (vec![], vec![]);
+    }
+
+    // Longest common subsequence of tokens, computed from the back
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if old_line[old_tokens[i].0..old_tokens[i].1]
+                == new_line[new_tokens[j].0..new_tokens[j].1]
+            {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut old_common = vec![false; n];
+    let mut new_common = vec![false; m];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old_line[old_tokens[i].0..old_tokens[i].1] == new_line[new_tokens[j].0..new_tokens[j].1]
+        {
+            old_common[i] = true;
+            new_common[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    // Emphasizing everything would be just noise for lines that have nothing in common
+    let has_common = old_common
+        .iter()
+        .zip(&old_tokens)
+        .any(|(common, (start, end))| *common && !old_line[*start..*end].trim().is_empty());
+    if !has_common {
+        return (vec![], vec![]);
+    }
+
+    (
+        changed_ranges(&old_tokens, &old_common),
+        changed_ranges(&new_tokens, &new_common),
+    )
+}
+
+/// Merges consecutive changed tokens into byte ranges, shifted by the leading `+`/`-`
+fn changed_ranges(tokens: &[(usize, usize)], common: &[bool]) -> ChangedRanges {
+    let mut ranges: ChangedRanges = vec![];
+    for ((start, end), common) in tokens.iter().zip(common) {
+        if *common {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(last) if last.1 == start + 1 => last.1 = end + 1,
+            _ => ranges.push((start + 1, end + 1)),
+        }
+    }
+    ranges
+}
+
+/// Splits a line into words, whitespace runs and single other characters, as byte ranges
+fn tokenize(line: &str) -> Vec<(usize, usize)> {
+    let mut tokens = vec![];
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        if is_word_char(c) || c.is_whitespace() {
+            let word = is_word_char(c);
+            while let Some((idx, next)) = chars.peek() {
+                if (word && is_word_char(*next)) || (!word && next.is_whitespace()) {
+                    end = idx + next.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+        }
+        tokens.push((start, end));
+    }
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[(&str, Option<u32>, Option<u32>)]) -> DiffLines {
+        lines
+            .iter()
+            .map(|(text, old, new)| (text.to_string(), *old, *new))
+            .collect()
+    }
+
+    fn texts<'a>(line: &'a str, tokens: &[(usize, usize)]) -> Vec<&'a str> {
+        tokens.iter().map(|(s, e)| &line[*s..*e]).collect()
+    }
+
+    #[test]
+    fn split_pairs_removed_and_added_lines() {
+        let diffs = lines(&[
+            ("@@ -1,3 +1,4 @@", None, None),
+            (" a", Some(1), Some(1)),
+            ("-b", Some(2), None),
+            ("-c", Some(3), None),
+            ("+B", None, Some(2)),
+            ("+C", None, Some(3)),
+            ("+D", None, Some(4)),
+            (" e", Some(4), Some(5)),
+        ]);
+        assert_eq!(
+            split_lines(&diffs),
+            vec![
+                SplitLine::Header(0),
+                SplitLine::Lines(Some(1), Some(1)),
+                SplitLine::Lines(Some(2), Some(4)),
+                SplitLine::Lines(Some(3), Some(5)),
+                SplitLine::Lines(None, Some(6)),
+                SplitLine::Lines(Some(7), Some(7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_flushes_changes_at_headers_and_end() {
+        let diffs = lines(&[
+            ("-a", Some(1), None),
+            ("@@ -5 +5 @@", None, None),
+            ("+b", None, Some(5)),
+        ]);
+        assert_eq!(
+            split_lines(&diffs),
+            vec![
+                SplitLine::Lines(Some(0), None),
+                SplitLine::Header(1),
+                SplitLine::Lines(None, Some(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_words_whitespace_and_symbols() {
+        let line = "let x_1 =  foo(äb);";
+        assert_eq!(
+            texts(line, &tokenize(line)),
+            vec!["let", " ", "x_1", " ", "=", "  ", "foo", "(", "äb", ")", ";"]
+        );
+        assert!(tokenize("").is_empty());
+    }
+
+    #[test]
+    fn word_changes_of_paired_lines() {
+        let diffs = lines(&[
+            ("-let x = foo(a);", Some(1), None),
+            ("+let x = bar(a, b);", None, Some(1)),
+            (" unchanged", Some(2), Some(2)),
+        ]);
+        let changes = word_changes(&diffs);
+        assert_eq!(texts(&diffs[0].0, &changes[0]), vec!["foo"]);
+        assert_eq!(texts(&diffs[1].0, &changes[1]), vec!["bar", ", b"]);
+        assert!(changes[2].is_empty());
+    }
+
+    #[test]
+    fn no_word_changes_without_common_words() {
+        let diffs = lines(&[
+            ("-alpha beta", Some(1), None),
+            ("+gamma delta", None, Some(1)),
+            ("+extra", None, Some(2)),
+        ]);
+        assert!(word_changes(&diffs).iter().all(|ranges| ranges.is_empty()));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::util::ctrl_chars::CtrlChars;
+use std::path::Path;
+use std::str::FromStr;
+use tui::style::Color;
+use unicode_width::UnicodeWidthStr;
+
+/// Font size of exported graphs, in pixels
+const FONT_SIZE: f32 = 14.0;
+/// Width of a character in exported SVGs, relative to the font size
+const CHAR_WIDTH: f32 = 0.6;
+/// Height of a line in exported graphs, relative to the font size
+const LINE_HEIGHT: f32 = 1.3;
+/// Padding around exported SVGs, in pixels
+const PADDING: f32 = 10.0;
+const BACKGROUND_COLOR: &str = "#1e1e1e";
+const FOREGROUND_COLOR: &str = "#d4d4d4";
+const FONT_FAMILY: &str = "'DejaVu Sans Mono', Menlo, Consolas, monospace";
+
+/// File format for exporting the graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Svg,
+    Html,
+}
+
+impl ExportFormat {
+    /// The format for a file name, by its extension
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match extension.as_str() {
+            "svg" => Ok(ExportFormat::Svg),
+            "html" | "htm" => Ok(ExportFormat::Html),
+            _ => Err(format!(
+                "Unable to export to '{}'. Supports file extensions [svg|html].",
+                path.display()
+            )),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "svg" => Ok(ExportFormat::Svg),
+            "html" => Ok(ExportFormat::Html),
+            other => Err(format!(
+                "Unknown export format '{}'. Supports [svg|html].",
+                other
+            )),
+        }
+    }
+}
+
+/// A piece of text with a uniform color, or `None` for the default color
+type Chunk = (Option<&'static str>, String);
+
+/// Renders graph and text lines, as produced by `print_unicode`, to a standalone document.
+/// Colors are taken from the terminal color codes in the lines.
+pub fn export_graph(
+    graph_lines: &[String],
+    text_lines: &[String],
+    format: ExportFormat,
+    title: &str,
+) -> String {
+    let lines: Vec<Vec<Chunk>> = graph_lines
+        .iter()
+        .zip(text_lines.iter())
+        .map(|(graph, text)| {
+            let mut chunks = to_chunks(graph);
+            chunks.extend(to_chunks(&format!("  {}", text)));
+            chunks
+        })
+        .collect();
+
+    match format {
+        ExportFormat::Svg => to_svg(&lines, title),
+        ExportFormat::Html => to_html(&lines, title),
+    }
+}
+
+fn to_chunks(line: &str) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    for text in CtrlChars::parse(line).into_text() {
+        for spans in text.lines {
+            for span in spans.0 {
+                if !span.content.is_empty() {
+                    chunks.push((span.style.fg.and_then(to_hex), span.content.into_owned()));
+                }
+            }
+        }
+    }
+    chunks
+}
+
+fn to_svg(lines: &[Vec<Chunk>], title: &str) -> String {
+    let char_width = FONT_SIZE * CHAR_WIDTH;
+    let line_height = FONT_SIZE * LINE_HEIGHT;
+    let max_chars = lines
+        .iter()
+        .map(|line| line.iter().map(|(_, text)| text.width()).sum::<usize>())
+        .max()
+        .unwrap_or(0);
+    let width = max_chars as f32 * char_width + 2.0 * PADDING;
+    let height = lines.len() as f32 * line_height + 2.0 * PADDING;
+
+    let mut svg = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w:.0}\" height=\"{h:.0}\" viewBox=\"0 0 {w:.0} {h:.0}\">\n\
+         <title>{}</title>\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n\
+         <g font-family=\"{}\" font-size=\"{}\" fill=\"{}\" xml:space=\"preserve\">\n",
+        escape(title),
+        BACKGROUND_COLOR,
+        FONT_FAMILY,
+        FONT_SIZE,
+        FOREGROUND_COLOR,
+        w = width,
+        h = height,
+    );
+    for (idx, line) in lines.iter().enumerate() {
+        let y = PADDING + (idx as f32 + 0.8) * line_height;
+        svg.push_str(&format!("<text y=\"{:.1}\">", y));
+        // Each chunk is positioned explicitly, so that font metrics do not shift the graph
+        let mut column = 0;
+        for (color, text) in line {
+            let x = PADDING + column as f32 * char_width;
+            match color {
+                Some(color) => svg.push_str(&format!(
+                    "<tspan x=\"{:.1}\" fill=\"{}\">{}</tspan>",
+                    x,
+                    color,
+                    escape(text)
+                )),
+                None => svg.push_str(&format!("<tspan x=\"{:.1}\">{}</tspan>", x, escape(text))),
+            }
+            column += text.width();
+        }
+        svg.push_str("</text>\n");
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+fn to_html(lines: &[Vec<Chunk>], title: &str) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>{}</title>\n\
+         <style>\n\
+         body {{ margin: 0; background: {bg}; }}\n\
+         pre {{ margin: 0; padding: {}px; background: {bg}; color: {}; font-family: {}; font-size: {}px; line-height: {}; }}\n\
+         </style>\n\
+         </head>\n\
+         <body>\n\
+         <pre>",
+        escape(title),
+        PADDING,
+        FOREGROUND_COLOR,
+        FONT_FAMILY,
+        FONT_SIZE,
+        LINE_HEIGHT,
+        bg = BACKGROUND_COLOR,
+    );
+    for line in lines {
+        for (color, text) in line {
+            match color {
+                Some(color) => html.push_str(&format!(
+                    "<span style=\"color: {}\">{}</span>",
+                    color,
+                    escape(text)
+                )),
+                None => html.push_str(&escape(text)),
+            }
+        }
+        html.push('\n');
+    }
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+/// Hex code of a terminal color, using the xterm palette
+fn to_hex(color: Color) -> Option<&'static str> {
+    match color {
+        Color::Black => Some("#000000"),
+        Color::Red => Some("#cd0000"),
+        Color::Green => Some("#00cd00"),
+        Color::Yellow => Some("#cdcd00"),
+        Color::Blue => Some("#0000ee"),
+        Color::Magenta => Some("#cd00cd"),
+        Color::Cyan => Some("#00cdcd"),
+        Color::Gray | Color::White => Some("#e5e5e5"),
+        Color::DarkGray => Some("#7f7f7f"),
+        Color::LightRed => Some("#ff0000"),
+        Color::LightGreen => Some("#00ff00"),
+        Color::LightYellow => Some("#ffff00"),
+        Color::LightBlue => Some("#5c5cff"),
+        Color::LightMagenta => Some("#ff00ff"),
+        Color::LightCyan => Some("#00ffff"),
+        _ => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,71 @@
+use std::env;
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// An external program to run in the terminal, while the UI is suspended
+pub struct ExternalCommand {
+    pub command: Command,
+    /// Text written to the program's stdin
+    pub input: Option<String>,
+}
+
+impl ExternalCommand {
+    pub fn new(command: Command) -> Self {
+        Self {
+            command,
+            input: None,
+        }
+    }
+
+    pub fn input(mut self, input: String) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    /// Runs the program and waits until it exits
+    pub fn run(mut self) -> Result<(), String> {
+        let program = self.command.get_program().to_string_lossy().to_string();
+        let error = |err: &dyn std::fmt::Display| format!("Unable to run '{}'\n{}", program, err);
+
+        if self.input.is_some() {
+            self.command.stdin(Stdio::piped());
+        }
+        let mut child = self.command.spawn().map_err(|err| error(&err))?;
+        if let (Some(mut stdin), Some(input)) = (child.stdin.take(), &self.input) {
+            match stdin.write_all(input.as_bytes()) {
+                // The program exited without reading everything, e.g. a pager quit early
+                Err(err) if err.kind() != ErrorKind::BrokenPipe => return Err(error(&err)),
+                _ => {}
+            }
+        }
+        let status = child.wait().map_err(|err| error(&err))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(error(&status))
+        }
+    }
+}
+
+/// The user's editor from `$VISUAL` or `$EDITOR`, or `vi`
+pub fn editor() -> Command {
+    from_env(&["VISUAL", "EDITOR"], "vi")
+}
+
+/// The user's pager from `$PAGER`, or `less`
+pub fn pager() -> Command {
+    from_env(&["PAGER"], "less")
+}
+
+/// A command from the first non-empty environment variable, which may contain arguments
+fn from_env(vars: &[&str], default: &str) -> Command {
+    let value = vars
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| default.to_string());
+    let mut parts = value.split_whitespace();
+    let mut command = Command::new(parts.next().unwrap_or(default));
+    command.args(parts);
+    command
+}
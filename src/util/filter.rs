@@ -0,0 +1,578 @@
+use crate::util::git::resolve_commit;
+use git2::{Commit, Delta, DiffFindOptions, DiffOptions, ErrorCode, Oid, Repository, Tree};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+/// Prefixes of the `key:value` parts of a filter
+const KEYS: &[&str] = &["author:", "since:", "until:", "path:", "range:"];
+
+/// Filter for commits by author, date range, message, paths and revision range.
+///
+/// All criteria must match. Text matching is case-insensitive.
+/// Paths and the revision range are not checked by `matches`, as the graph is restricted
+/// to them, see `GraphLoader`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommitFilter {
+    /// Part of the author's name or email
+    pub author: Option<String>,
+    /// Earliest commit date, in seconds since the epoch
+    pub since: Option<i64>,
+    /// Latest commit date, in seconds since the epoch
+    pub until: Option<i64>,
+    /// Part of the commit message
+    pub grep: Option<String>,
+    /// Pathspecs of files or directories the commits must touch
+    pub paths: Vec<String>,
+    /// Revision range the commits must be in
+    pub range: Option<RevisionRange>,
+}
+
+impl CommitFilter {
+    /// Creates a filter from optional CLI arguments. Dates are in format `YYYY-MM-DD`,
+    /// ranges in format `A..B` or `A...B`.
+    pub fn from_args(
+        author: Option<&str>,
+        since: Option<&str>,
+        until: Option<&str>,
+        grep: Option<&str>,
+        paths: Vec<String>,
+        range: Option<&str>,
+    ) -> Result<Self, String> {
+        Ok(CommitFilter {
+            author: author.map(|a| a.to_string()),
+            since: since.map(|s| parse_date(s, false)).transpose()?,
+            until: until.map(|u| parse_date(u, true)).transpose()?,
+            grep: grep.map(|g| g.to_string()),
+            paths,
+            range: range.map(RevisionRange::parse).transpose()?,
+        })
+    }
+
+    /// Parses a filter of the form
+    /// `author:<name> since:<date> until:<date> path:<path> range:<A..B> <message>`.
+    /// All parts are optional, `path:` can be given multiple times.
+    /// Values containing spaces can be quoted. Quoted words like `"path:x"` are part of the message.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut filter = CommitFilter::default();
+        let mut message = vec![];
+        for (token, quoted) in tokenize(text) {
+            if quoted {
+                message.push(token);
+            } else if let Some(author) = token.strip_prefix("author:") {
+                filter.author = Some(author.to_string());
+            } else if let Some(since) = token.strip_prefix("since:") {
+                filter.since = Some(parse_date(since, false)?);
+            } else if let Some(until) = token.strip_prefix("until:") {
+                filter.until = Some(parse_date(until, true)?);
+            } else if let Some(path) = token.strip_prefix("path:") {
+                filter.paths.push(path.to_string());
+            } else if let Some(range) = token.strip_prefix("range:") {
+                filter.range = Some(RevisionRange::parse(range)?);
+            } else {
+                message.push(token);
+            }
+        }
+        if !message.is_empty() {
+            filter.grep = Some(message.join(" "));
+        }
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.author.is_none()
+            && self.since.is_none()
+            && self.until.is_none()
+            && self.grep.is_none()
+            && self.paths.is_empty()
+            && self.range.is_none()
+    }
+
+    /// Whether commits not matching the filter are dimmed.
+    /// Commits outside the revision range or not touching the paths are not shown at all.
+    pub fn dims_commits(&self) -> bool {
+        self.author.is_some() || self.since.is_some() || self.until.is_some() || self.grep.is_some()
+    }
+
+    pub fn matches(&self, commit: &Commit) -> bool {
+        if let Some(author) = &self.author {
+            let author = author.to_lowercase();
+            let signature = commit.author();
+            let name = signature.name().unwrap_or("").to_lowercase();
+            let email = signature.email().unwrap_or("").to_lowercase();
+            if !name.contains(&author) && !email.contains(&author) {
+                return false;
+            }
+        }
+        let time = commit.time().seconds();
+        if self.since.map(|since| time < since).unwrap_or(false)
+            || self.until.map(|until| time > until).unwrap_or(false)
+        {
+            return false;
+        }
+        if let Some(grep) = &self.grep {
+            let message = commit.message().unwrap_or("").to_lowercase();
+            if !message.contains(&grep.to_lowercase()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl fmt::Display for CommitFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(author) = &self.author {
+            parts.push(format!("author:{}", quote(author)));
+        }
+        if let Some(since) = self.since {
+            parts.push(format!("since:{}", format_date(since)));
+        }
+        if let Some(until) = self.until {
+            parts.push(format!("until:{}", format_date(until)));
+        }
+        for path in &self.paths {
+            parts.push(format!("path:{}", quote(path)));
+        }
+        if let Some(range) = &self.range {
+            parts.push(format!("range:{}", quote(&range.to_string())));
+        }
+        if let Some(grep) = &self.grep {
+            // Words looking like keys are quoted, so that they are parsed as part of the message
+            let words: Vec<_> = grep
+                .split_whitespace()
+                .map(|word| {
+                    if KEYS.iter().any(|key| word.starts_with(key)) {
+                        format!("\"{}\"", word)
+                    } else {
+                        word.to_string()
+                    }
+                })
+                .collect();
+            parts.push(words.join(" "));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// A revision range `A..B`, i.e. commits reachable from `B` but not from `A`,
+/// or `A...B`, i.e. commits reachable from either `A` or `B`, but not from both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevisionRange {
+    pub from: String,
+    pub to: String,
+    /// Whether this is a three-dot range `A...B`
+    pub symmetric: bool,
+}
+
+impl RevisionRange {
+    /// Parses a range `A..B` or `A...B`. An omitted revision defaults to `HEAD`, like in Git.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (from, to, symmetric) = if let Some((from, to)) = text.split_once("...") {
+            (from, to, true)
+        } else if let Some((from, to)) = text.split_once("..") {
+            (from, to, false)
+        } else {
+            return Err(format!(
+                "Invalid revision range '{}'. Expected format A..B or A...B",
+                text
+            ));
+        };
+        let or_head = |rev: &str| {
+            if rev.is_empty() {
+                "HEAD".to_string()
+            } else {
+                rev.to_string()
+            }
+        };
+        Ok(RevisionRange {
+            from: or_head(from),
+            to: or_head(to),
+            symmetric,
+        })
+    }
+
+    /// The commits in the range
+    pub fn commits(&self, repository: &Repository) -> Result<HashSet<Oid>, String> {
+        let from = resolve_commit(repository, &self.from)?;
+        let to = resolve_commit(repository, &self.to)?;
+        let mut walk = repository
+            .revwalk()
+            .map_err(|err| err.message().to_string())?;
+        walk.push(to).map_err(|err| err.message().to_string())?;
+        if self.symmetric {
+            walk.push(from).map_err(|err| err.message().to_string())?;
+            match repository.merge_bases(from, to) {
+                Ok(bases) => {
+                    for base in bases.iter() {
+                        walk.hide(*base).map_err(|err| err.message().to_string())?;
+                    }
+                }
+                Err(err) if err.code() == ErrorCode::NotFound => {}
+                Err(err) => return Err(err.message().to_string()),
+            }
+        } else {
+            walk.hide(from).map_err(|err| err.message().to_string())?;
+        }
+        walk.map(|oid| oid.map_err(|err| err.message().to_string()))
+            .collect()
+    }
+
+    /// The commits to compare, i.e. `A` and `B`, or the merge base of `A` and `B` and `B`
+    /// for a three-dot range
+    pub fn endpoints(&self, repository: &Repository) -> Result<(Oid, Oid), String> {
+        let from = resolve_commit(repository, &self.from)?;
+        let to = resolve_commit(repository, &self.to)?;
+        if self.symmetric {
+            let base = repository.merge_base(from, to).map_err(|err| {
+                format!(
+                    "No merge base of '{}' and '{}'\n{}",
+                    self.from,
+                    self.to,
+                    err.message()
+                )
+            })?;
+            Ok((base, to))
+        } else {
+            Ok((from, to))
+        }
+    }
+
+    /// Name of the commit `B` is compared to
+    pub fn compare_name(&self) -> String {
+        if self.symmetric {
+            format!("merge base of {} and {}", self.from, self.to)
+        } else {
+            self.from.clone()
+        }
+    }
+}
+
+impl fmt::Display for RevisionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dots = if self.symmetric { "..." } else { ".." };
+        write!(f, "{}{}{}", self.from, dots, self.to)
+    }
+}
+
+/// The commits touching the given paths, like `git log -- <paths>`.
+///
+/// Merges are included only if they differ from all parents in the paths.
+/// A single tracked file is followed through renames.
+/// Commits are processed incrementally, so that a growing graph only adds the new commits.
+#[derive(Default, Clone)]
+pub struct PathHistory {
+    /// Pathspecs the history was started with
+    pub paths: Vec<String>,
+    /// Tracked paths after following renames
+    tracked: Vec<String>,
+    /// The touched file (under its name in that commit) for each matching commit
+    pub commits: HashMap<Oid, String>,
+    visited: HashSet<Oid>,
+}
+
+impl PathHistory {
+    pub fn new(paths: Vec<String>) -> Self {
+        PathHistory {
+            tracked: paths.clone(),
+            paths,
+            commits: HashMap::new(),
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Processes the commits not processed before.
+    /// `commits` must be ordered from newest to oldest.
+    pub fn update(
+        &mut self,
+        repository: &Repository,
+        commits: impl Iterator<Item = Oid>,
+    ) -> Result<(), String> {
+        let commits: Vec<Oid> = commits.collect();
+        // Renames are followed from newest to oldest, so new commits
+        // before known ones (e.g. after a reload) require starting over
+        if let Some(first_new) = commits.iter().position(|oid| !self.visited.contains(oid)) {
+            if commits[first_new..]
+                .iter()
+                .any(|oid| self.visited.contains(oid))
+            {
+                *self = PathHistory::new(std::mem::take(&mut self.paths));
+            }
+        }
+        for oid in commits {
+            if self.visited.insert(oid) {
+                self.add_commit(repository, oid)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_commit(&mut self, repository: &Repository, oid: Oid) -> Result<(), String> {
+        let tracked = &mut self.tracked;
+        let commit = repository
+            .find_commit(oid)
+            .map_err(|err| err.message().to_string())?;
+        let tree = commit.tree().map_err(|err| err.message().to_string())?;
+        let parents = commit
+            .parents()
+            .map(|parent| parent.tree())
+            .collect::<Result<Vec<Tree>, _>>()
+            .map_err(|err| err.message().to_string())?;
+
+        let mut opts = DiffOptions::new();
+        for path in tracked.iter() {
+            opts.pathspec(path);
+        }
+        let diffs = if parents.is_empty() {
+            vec![repository.diff_tree_to_tree(None, Some(&tree), Some(&mut opts))]
+        } else {
+            parents
+                .iter()
+                .map(|parent| {
+                    repository.diff_tree_to_tree(Some(parent), Some(&tree), Some(&mut opts))
+                })
+                .collect()
+        }
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.message().to_string())?;
+
+        if diffs.iter().any(|diff| diff.deltas().len() == 0) {
+            return Ok(());
+        }
+
+        let first = &diffs[0];
+        if let Some(path) = first
+            .deltas()
+            .next()
+            .and_then(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+            .and_then(|path| path.to_str())
+        {
+            self.commits.insert(oid, path.to_string());
+        }
+
+        if tracked.len() == 1 && !parents.is_empty() {
+            let file = Path::new(&tracked[0]);
+            let added = first.deltas().any(|delta| {
+                delta.status() == Delta::Added && delta.new_file().path() == Some(file)
+            });
+            if added {
+                if let Some(old_path) = renamed_from(repository, &parents[0], &tree, file)? {
+                    *tracked = vec![old_path];
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds the previous name of a file added between two trees
+fn renamed_from(
+    repository: &Repository,
+    old_tree: &Tree,
+    new_tree: &Tree,
+    file: &Path,
+) -> Result<Option<String>, String> {
+    let mut diff = repository
+        .diff_tree_to_tree(Some(old_tree), Some(new_tree), None)
+        .map_err(|err| err.message().to_string())?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(|err| err.message().to_string())?;
+
+    let old_path = diff
+        .deltas()
+        .find(|delta| delta.status() == Delta::Renamed && delta.new_file().path() == Some(file))
+        .and_then(|delta| delta.old_file().path().and_then(|p| p.to_str()))
+        .map(|path| path.to_string());
+    Ok(old_path)
+}
+
+/// Splits at whitespace, except inside double quotes. Quotes are removed.
+/// Returns each token, and whether it starts with a quote.
+fn tokenize(text: &str) -> Vec<(String, bool)> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut starts_quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                if current.is_empty() && !quoted {
+                    starts_quoted = true;
+                }
+                quoted = !quoted;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push((std::mem::take(&mut current), starts_quoted));
+                }
+                starts_quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push((current, starts_quoted));
+    }
+    tokens
+}
+
+fn quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Parses a date in format `YYYY-MM-DD` (UTC) to seconds since the epoch.
+/// With `end_of_day`, returns the last second of the day.
+fn parse_date(date: &str, end_of_day: bool) -> Result<i64, String> {
+    let err = || format!("Invalid date '{}'. Expected format YYYY-MM-DD", date);
+    let parts = date
+        .split('-')
+        .map(|p| p.parse::<i64>().map_err(|_| err()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if parts.len() != 3 || !(1..=12).contains(&parts[1]) || !(1..=31).contains(&parts[2]) {
+        return Err(err());
+    }
+    let days = days_from_civil(parts[0], parts[1], parts[2]);
+    let offset = if end_of_day { SECONDS_PER_DAY - 1 } else { 0 };
+    Ok(days * SECONDS_PER_DAY + offset)
+}
+
+fn format_date(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Days since 1970-01-01 for a date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_from_civil_known_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 2, 29), 11016);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1900, 2, 28), -25509);
+        assert_eq!(days_from_civil(1900, 3, 1), -25508);
+        assert_eq!(days_from_civil(2024, 2, 29), 19782);
+        assert_eq!(days_from_civil(1600, 1, 1), -135140);
+    }
+
+    #[test]
+    fn civil_from_days_inverts_days_from_civil() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-25508), (1900, 3, 1));
+        for days in (-150_000..150_000).step_by(7) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn parse_date_start_and_end_of_day() {
+        assert_eq!(parse_date("2024-02-29", false), Ok(19782 * SECONDS_PER_DAY));
+        assert_eq!(
+            parse_date("2024-02-29", true),
+            Ok(19783 * SECONDS_PER_DAY - 1)
+        );
+        assert_eq!(format_date(19783 * SECONDS_PER_DAY - 1), "2024-02-29");
+        assert!(parse_date("2024-13-01", false).is_err());
+        assert!(parse_date("2024-01", false).is_err());
+        assert!(parse_date("yesterday", false).is_err());
+    }
+
+    #[test]
+    fn tokenize_quotes() {
+        assert_eq!(
+            tokenize(r#"author:"John Doe"  fix "path:x" "a b""#),
+            vec![
+                ("author:John Doe".to_string(), false),
+                ("fix".to_string(), false),
+                ("path:x".to_string(), true),
+                ("a b".to_string(), true),
+            ]
+        );
+        assert!(tokenize("  ").is_empty());
+    }
+
+    #[test]
+    fn parse_revision_ranges() {
+        let range = |text| RevisionRange::parse(text).map(|r| (r.from, r.to, r.symmetric));
+        let owned =
+            |from: &str, to: &str, symmetric| Ok((from.to_string(), to.to_string(), symmetric));
+        assert_eq!(range("main..feature"), owned("main", "feature", false));
+        assert_eq!(range("main...feature"), owned("main", "feature", true));
+        assert_eq!(range("..feature"), owned("HEAD", "feature", false));
+        assert_eq!(range("main..."), owned("main", "HEAD", true));
+        assert_eq!(range("HEAD~2.."), owned("HEAD~2", "HEAD", false));
+        assert!(range("main").is_err());
+    }
+
+    #[test]
+    fn parse_filter() {
+        let filter = CommitFilter::parse(
+            r#"author:"John Doe" since:2024-01-01 path:src range:a..b fix bug"#,
+        )
+        .unwrap();
+        assert_eq!(filter.author.as_deref(), Some("John Doe"));
+        assert_eq!(filter.since, Some(19723 * SECONDS_PER_DAY));
+        assert_eq!(filter.until, None);
+        assert_eq!(filter.paths, vec!["src".to_string()]);
+        assert_eq!(
+            filter.range.as_ref().map(|r| r.to_string()).as_deref(),
+            Some("a..b")
+        );
+        assert_eq!(filter.grep.as_deref(), Some("fix bug"));
+        assert!(CommitFilter::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn filter_display_round_trip() {
+        let filter = CommitFilter {
+            author: Some("John Doe".to_string()),
+            since: Some(parse_date("2024-01-01", false).unwrap()),
+            until: Some(parse_date("2024-12-31", true).unwrap()),
+            grep: Some("fix author:x in path:y".to_string()),
+            paths: vec!["src/my file.rs".to_string()],
+            range: Some(RevisionRange::parse("main...feature").unwrap()),
+        };
+        assert_eq!(CommitFilter::parse(&filter.to_string()), Ok(filter));
+    }
+}
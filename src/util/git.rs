@@ -0,0 +1,393 @@
+use git2::build::CheckoutBuilder;
+use git2::{
+    BranchType, CherrypickOptions, Oid, Repository, RevertOptions, Signature, StashApplyOptions,
+    Time,
+};
+
+/// Checks out a commit as detached HEAD. Fails if local changes would be overwritten.
+pub fn checkout_commit(repository: &Repository, oid: Oid) -> Result<(), String> {
+    let commit = repository
+        .find_commit(oid)
+        .map_err(|err| err.message().to_string())?;
+    repository
+        .checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))
+        .map_err(|err| err.message().to_string())?;
+    repository
+        .set_head_detached(oid)
+        .map_err(|err| err.message().to_string())
+}
+
+/// Checks out a local branch. Fails if local changes would be overwritten.
+pub fn checkout_branch(repository: &Repository, name: &str) -> Result<(), String> {
+    let branch = repository
+        .find_branch(name, BranchType::Local)
+        .map_err(|err| err.message().to_string())?;
+    let reference = branch.get();
+    let commit = reference
+        .peel_to_commit()
+        .map_err(|err| err.message().to_string())?;
+    let ref_name = reference
+        .name()
+        .ok_or_else(|| format!("Invalid branch name '{}'", name))?;
+
+    repository
+        .checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))
+        .map_err(|err| err.message().to_string())?;
+    repository
+        .set_head(ref_name)
+        .map_err(|err| err.message().to_string())
+}
+
+/// Resolves a branch, tag, commit hash or other revision like `HEAD~2` to a commit
+pub fn resolve_commit(repository: &Repository, revision: &str) -> Result<Oid, String> {
+    repository
+        .revparse_single(revision)
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .map_err(|err| format!("Unable to resolve '{}'\n{}", revision, err.message()))
+}
+
+/// Creates a local branch at a commit
+pub fn create_branch(repository: &Repository, oid: Oid, name: &str) -> Result<(), String> {
+    let commit = repository
+        .find_commit(oid)
+        .map_err(|err| err.message().to_string())?;
+    repository
+        .branch(name, &commit, false)
+        .map_err(|err| err.message().to_string())?;
+    Ok(())
+}
+
+/// Deletes a local branch
+pub fn delete_branch(repository: &Repository, name: &str) -> Result<(), String> {
+    let mut branch = repository
+        .find_branch(name, BranchType::Local)
+        .map_err(|err| err.message().to_string())?;
+    branch.delete().map_err(|err| err.message().to_string())
+}
+
+/// Creates a tag at a commit. The tag is annotated if a message is given, lightweight otherwise.
+pub fn create_tag(
+    repository: &Repository,
+    oid: Oid,
+    name: &str,
+    message: Option<&str>,
+) -> Result<(), String> {
+    let object = repository
+        .find_object(oid, None)
+        .map_err(|err| err.message().to_string())?;
+    match message {
+        Some(message) => {
+            let signature = repository
+                .signature()
+                .map_err(|err| err.message().to_string())?;
+            repository.tag(name, &object, &signature, message, false)
+        }
+        None => repository.tag_lightweight(name, &object, false),
+    }
+    .map_err(|err| err.message().to_string())?;
+    Ok(())
+}
+
+/// Applies the changes of a commit to HEAD and commits them with the commit's author and message.
+/// For merge commits, `mainline` is the number of the parent (starting at 1) the changes are
+/// relative to. Returns the conflicted paths if there are conflicts, without committing.
+/// Fails if the index contains staged changes, as they would be committed too.
+pub fn cherry_pick(
+    repository: &Repository,
+    oid: Oid,
+    mainline: Option<u32>,
+) -> Result<Vec<String>, String> {
+    check_no_staged_changes(repository, "cherry-pick")?;
+    let commit = repository
+        .find_commit(oid)
+        .map_err(|err| err.message().to_string())?;
+    let mut options = CherrypickOptions::new();
+    if let Some(mainline) = mainline {
+        options.mainline(mainline);
+    }
+    repository
+        .cherrypick(&commit, Some(&mut options))
+        .map_err(|err| err.message().to_string())?;
+    let author = commit.author();
+    commit_applied(
+        repository,
+        oid,
+        &author,
+        commit.message().unwrap_or_default(),
+    )
+}
+
+/// Commits the reverse of a commit's changes to HEAD.
+/// For merge commits, `mainline` is the number of the parent (starting at 1) to return to.
+/// Returns the conflicted paths if there are conflicts, without committing.
+/// Fails if the index contains staged changes, as they would be committed too.
+pub fn revert(
+    repository: &Repository,
+    oid: Oid,
+    mainline: Option<u32>,
+) -> Result<Vec<String>, String> {
+    check_no_staged_changes(repository, "revert")?;
+    let commit = repository
+        .find_commit(oid)
+        .map_err(|err| err.message().to_string())?;
+    let mut options = RevertOptions::new();
+    if let Some(mainline) = mainline {
+        options.mainline(mainline);
+    }
+    repository
+        .revert(&commit, Some(&mut options))
+        .map_err(|err| err.message().to_string())?;
+
+    let mut message = format!(
+        "Revert \"{}\"\n\nThis reverts commit {}",
+        commit.summary().unwrap_or_default(),
+        oid
+    );
+    match mainline.and_then(|mainline| commit.parent_id(mainline as usize - 1).ok()) {
+        Some(parent) => message.push_str(&format!(", reversing\nchanges made to {}.\n", parent)),
+        None => message.push_str(".\n"),
+    }
+    let author = repository
+        .signature()
+        .map_err(|err| err.message().to_string())?;
+    commit_applied(repository, oid, &author, &message)
+}
+
+/// Fails if the index differs from HEAD, like `git cherry-pick` and `git revert` do
+fn check_no_staged_changes(repository: &Repository, operation: &str) -> Result<(), String> {
+    let head_tree = repository
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .map_err(|err| err.message().to_string())?;
+    let diff = repository
+        .diff_tree_to_index(Some(&head_tree), None, None)
+        .map_err(|err| err.message().to_string())?;
+    if diff.deltas().len() > 0 {
+        return Err(format!(
+            "Unable to {}, there are staged changes.\nCommit or unstage them first.",
+            operation
+        ));
+    }
+    Ok(())
+}
+
+/// Commits the index after a cherry-pick or revert, or returns the conflicted paths
+fn commit_applied(
+    repository: &Repository,
+    oid: Oid,
+    author: &Signature,
+    message: &str,
+) -> Result<Vec<String>, String> {
+    let mut index = repository
+        .index()
+        .map_err(|err| err.message().to_string())?;
+    if index.has_conflicts() {
+        let conflicts = index.conflicts().map_err(|err| err.message().to_string())?;
+        let mut paths = vec![];
+        for conflict in conflicts {
+            let conflict = conflict.map_err(|err| err.message().to_string())?;
+            if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+                paths.push(String::from_utf8_lossy(&entry.path).to_string());
+            }
+        }
+        return Ok(paths);
+    }
+
+    let head = repository
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|err| err.message().to_string())?;
+    let tree_oid = index
+        .write_tree()
+        .map_err(|err| err.message().to_string())?;
+    if tree_oid == head.tree_id() {
+        repository
+            .cleanup_state()
+            .map_err(|err| err.message().to_string())?;
+        return Err(format!(
+            "Nothing to commit, the changes of {} are already contained in HEAD",
+            &oid.to_string()[..7]
+        ));
+    }
+    let tree = repository
+        .find_tree(tree_oid)
+        .map_err(|err| err.message().to_string())?;
+    let committer = repository
+        .signature()
+        .map_err(|err| err.message().to_string())?;
+    repository
+        .commit(Some("HEAD"), author, &committer, message, &tree, &[&head])
+        .map_err(|err| err.message().to_string())?;
+    repository
+        .cleanup_state()
+        .map_err(|err| err.message().to_string())?;
+    Ok(vec![])
+}
+
+/// Entries of a reference's reflog, with old and new commit, message and time, latest first
+pub fn get_reflog(
+    repository: &Repository,
+    name: &str,
+) -> Result<Vec<(Oid, Oid, String, Time)>, String> {
+    let reflog = repository
+        .reflog(name)
+        .map_err(|err| err.message().to_string())?;
+    Ok(reflog
+        .iter()
+        .map(|entry| {
+            (
+                entry.id_old(),
+                entry.id_new(),
+                entry.message().unwrap_or_default().to_string(),
+                entry.committer().when(),
+            )
+        })
+        .collect())
+}
+
+/// Stashes of a repository, with their index, message and commit, latest first
+pub fn get_stashes(repository: &mut Repository) -> Result<Vec<(usize, String, Oid)>, String> {
+    let mut stashes = vec![];
+    repository
+        .stash_foreach(|index, message, oid| {
+            stashes.push((index, message.to_string(), *oid));
+            true
+        })
+        .map_err(|err| err.message().to_string())?;
+    Ok(stashes)
+}
+
+/// Applies a stash to the working tree, and removes it with `pop`.
+/// Fails if local changes would be overwritten.
+pub fn apply_stash(repository: &mut Repository, index: usize, pop: bool) -> Result<(), String> {
+    let mut options = StashApplyOptions::new();
+    if pop {
+        repository.stash_pop(index, Some(&mut options))
+    } else {
+        repository.stash_apply(index, Some(&mut options))
+    }
+    .map_err(|err| err.message().to_string())
+}
+
+/// Removes a stash
+pub fn drop_stash(repository: &mut Repository, index: usize) -> Result<(), String> {
+    repository
+        .stash_drop(index)
+        .map_err(|err| err.message().to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    /// Creates an empty repository in a fresh temporary directory
+    pub(crate) fn init_repository(name: &str) -> (PathBuf, Repository) {
+        let path = std::env::temp_dir().join(format!("git-igitt-{}-{}", name, std::process::id()));
+        if path.exists() {
+            std::fs::remove_dir_all(&path).unwrap();
+        }
+        let repository = Repository::init(&path).unwrap();
+        let mut config = repository.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        (path, repository)
+    }
+
+    /// Writes and stages a file
+    pub(crate) fn stage(repository: &Repository, workdir: &Path, file: &str, content: &str) {
+        std::fs::write(workdir.join(file), content).unwrap();
+        let mut index = repository.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+    }
+
+    /// Commits the index to HEAD, or to the given branch based on `parent`
+    pub(crate) fn commit(
+        repository: &Repository,
+        branch: Option<&str>,
+        parent: Option<Oid>,
+    ) -> Oid {
+        let mut index = repository.index().unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = repository.signature().unwrap();
+        let parent = parent.map(|oid| repository.find_commit(oid).unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        let reference = branch.map(|b| format!("refs/heads/{}", b));
+        repository
+            .commit(
+                Some(reference.as_deref().unwrap_or("HEAD")),
+                &signature,
+                &signature,
+                "message",
+                &tree,
+                &parents,
+            )
+            .unwrap()
+    }
+
+    fn head_contains(repository: &Repository, file: &str) -> bool {
+        let tree = repository.head().unwrap().peel_to_tree().unwrap();
+        tree.get_path(Path::new(file)).is_ok()
+    }
+
+    /// A repository with a commit adding `a.txt` on HEAD,
+    /// and a commit adding `b.txt` on branch `side`
+    fn repository_with_side_branch(name: &str) -> (PathBuf, Repository, Oid, Oid) {
+        let (path, repository) = init_repository(name);
+        stage(&repository, &path, "a.txt", "a");
+        let base = commit(&repository, None, None);
+        stage(&repository, &path, "b.txt", "b");
+        let side = commit(&repository, Some("side"), Some(base));
+        // Back to the state of HEAD
+        let mut index = repository.index().unwrap();
+        index.remove_path(Path::new("b.txt")).unwrap();
+        index.write().unwrap();
+        std::fs::remove_file(path.join("b.txt")).unwrap();
+        (path, repository, base, side)
+    }
+
+    #[test]
+    fn cherry_pick_commits_the_changes() {
+        let (path, repository, base, side) = repository_with_side_branch("cherry-pick");
+        assert_eq!(cherry_pick(&repository, side, None), Ok(vec![]));
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent_id(0).unwrap(), base);
+        assert!(head_contains(&repository, "b.txt"));
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn cherry_pick_refuses_staged_changes() {
+        let (path, repository, base, side) = repository_with_side_branch("cherry-pick-staged");
+        stage(&repository, &path, "staged.txt", "staged");
+        assert!(cherry_pick(&repository, side, None).is_err());
+        let head = repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.id(), base);
+        assert!(!head_contains(&repository, "staged.txt"));
+        // The staged file is left alone
+        let index = repository.index().unwrap();
+        assert!(index.get_path(Path::new("staged.txt"), 0).is_some());
+        assert!(index.get_path(Path::new("b.txt"), 0).is_none());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn revert_refuses_staged_changes() {
+        let (path, repository, base, _) = repository_with_side_branch("revert-staged");
+        stage(&repository, &path, "staged.txt", "staged");
+        assert!(revert(&repository, base, None).is_err());
+        assert_eq!(repository.head().unwrap().target(), Some(base));
+        assert!(head_contains(&repository, "a.txt"));
+
+        // Unstaged changes in other files are fine
+        let mut index = repository.index().unwrap();
+        index.remove_path(Path::new("staged.txt")).unwrap();
+        index.write().unwrap();
+        assert_eq!(revert(&repository, base, None), Ok(vec![]));
+        assert!(!head_contains(&repository, "a.txt"));
+        assert!(!head_contains(&repository, "staged.txt"));
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
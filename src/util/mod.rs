@@ -1,3 +1,10 @@
+pub mod clipboard;
 pub mod ctrl_chars;
+pub mod diff;
+pub mod export;
+pub mod external;
+pub mod filter;
 pub mod format;
+pub mod git;
+pub mod search;
 pub mod syntax_highlight;
@@ -0,0 +1,53 @@
+/// Lowercases text for case-insensitive searches.
+/// Characters are folded one by one, so that matches can be mapped back to the text.
+pub fn fold_case(text: &str) -> String {
+    text.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// Byte ranges in `text` of the case-insensitive matches of `term`
+pub fn find_matches(text: &str, term: &str) -> Vec<(usize, usize)> {
+    let term = fold_case(term);
+    if term.is_empty() {
+        return vec![];
+    }
+    // For each byte of the folded text, the range of the character it was folded from
+    let mut folded = String::with_capacity(text.len());
+    let mut origins = Vec::with_capacity(text.len());
+    for (start, c) in text.char_indices() {
+        let end = start + c.len_utf8();
+        for lower in c.to_lowercase() {
+            folded.push(lower);
+            origins.resize(folded.len(), (start, end));
+        }
+    }
+    folded
+        .match_indices(&term)
+        .map(|(start, m)| (origins[start].0, origins[start + m.len() - 1].1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_ascii_matches() {
+        assert_eq!(
+            find_matches("Fix fix FIX", "fix"),
+            vec![(0, 3), (4, 7), (8, 11)]
+        );
+        assert!(find_matches("abc", "").is_empty());
+        assert!(find_matches("abc", "d").is_empty());
+    }
+
+    #[test]
+    fn find_non_ascii_matches() {
+        assert_eq!(find_matches("Ärger ärger", "ÄR"), vec![(0, 3), (7, 10)]);
+        // 'İ' (2 bytes) folds to 'i' and a combining dot (3 bytes)
+        let text = "xİy";
+        assert_eq!(fold_case(text), "xi\u{307}y");
+        assert_eq!(find_matches(text, "i"), vec![(1, 3)]);
+        assert_eq!(find_matches(text, "Y"), vec![(3, 4)]);
+        assert!(fold_case(text).contains(&fold_case("İ")));
+    }
+}
@@ -1,28 +1,104 @@
-use lazy_static::lazy_static;
+use git2::Oid;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
 use syntect::easy::HighlightLines;
 use syntect::highlighting::Color as SynColor;
 use syntect::highlighting::{Style, Theme, ThemeSet};
-use syntect::parsing::SyntaxSet;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
 use tui::style::Color;
 use tui::text::{Span, Spans, Text};
 
-lazy_static! {
-    static ref SYNTAX: Syntax = Syntax {
-        syntax: SyntaxSet::load_defaults_nonewlines(),
-        themes: ThemeSet::load_defaults()
-    };
-    static ref THEME: Theme = create_custom_theme();
-}
+/// The default syntax theme. Its foreground is brightened for dark terminals.
+pub const DEFAULT_THEME: &str = "Solarized (dark)";
+
+static SYNTAX: OnceLock<Syntax> = OnceLock::new();
 
 struct Syntax {
     pub syntax: SyntaxSet,
-    pub themes: ThemeSet,
+    pub theme: Theme,
 }
 
-fn create_custom_theme() -> Theme {
-    let mut theme = SYNTAX.themes.themes["Solarized (dark)"].clone();
-    theme.settings.foreground = theme.settings.foreground.map(|color| brighter(color, 0.4)); //Some(syntect::highlighting::Color::WHITE);
-    theme
+/// Sets up syntax highlighting with the theme of the given name. Additional themes (`.tmTheme`)
+/// and syntax definitions (`.sublime-syntax`) are loaded from `dir`, if it exists.
+/// Without calling this, the default theme and syntax definitions are used.
+pub fn init(theme: &str, dir: &Path) -> Result<(), String> {
+    let syntax = load(theme, Some(dir))?;
+    // Ignored if highlighting was already used, e.g. when called twice
+    let _ = SYNTAX.set(syntax);
+    Ok(())
+}
+
+fn syntax() -> &'static Syntax {
+    SYNTAX.get_or_init(|| load(DEFAULT_THEME, None).expect("Default syntax theme not found"))
+}
+
+fn load(theme: &str, dir: Option<&Path>) -> Result<Syntax, String> {
+    let mut themes = ThemeSet::load_defaults();
+    let mut syntax = SyntaxSet::load_defaults_nonewlines();
+
+    if let Some(dir) = dir.filter(|dir| dir.is_dir()) {
+        let error = |err: &dyn std::fmt::Display| {
+            format!(
+                "Unable to load syntax highlighting files from {}\n{}",
+                dir.display(),
+                err
+            )
+        };
+        themes.add_from_folder(dir).map_err(|err| error(&err))?;
+
+        let mut builder = syntax.into_builder();
+        let default_count = builder.syntaxes().len();
+        builder
+            .add_from_folder(dir, false)
+            .map_err(|err| error(&err))?;
+        // Rebuilding the syntax set is expensive, so only do it for custom syntax definitions
+        syntax = if builder.syntaxes().len() > default_count {
+            builder.build()
+        } else {
+            SyntaxSet::load_defaults_nonewlines()
+        };
+    }
+
+    let mut theme_def = themes.themes.remove(theme).ok_or_else(|| {
+        format!(
+            "Unknown syntax theme '{}'. Available themes:\n  {}",
+            theme,
+            themes
+                .themes
+                .keys()
+                .map(|name| name.as_str())
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        )
+    })?;
+    if theme == DEFAULT_THEME {
+        theme_def.settings.foreground = theme_def
+            .settings
+            .foreground
+            .map(|color| brighter(color, 0.4));
+    }
+
+    Ok(Syntax {
+        syntax,
+        theme: theme_def,
+    })
+}
+
+/// The syntax of a file, by its file name (e.g. `Makefile`), its extension,
+/// or its first line (e.g. `#!/bin/sh`)
+fn find_syntax(path: &str, text: &str) -> Option<&'static SyntaxReference> {
+    let syntax = &self::syntax().syntax;
+    let path = Path::new(path);
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| syntax.find_syntax_by_extension(name))
+        .or_else(|| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(|ext| syntax.find_syntax_by_extension(ext))
+        })
+        .or_else(|| syntax.find_syntax_by_first_line(text.lines().next()?))
 }
 
 fn brighter(color: SynColor, factor: f32) -> SynColor {
@@ -34,17 +110,18 @@ fn brighter(color: SynColor, factor: f32) -> SynColor {
     }
 }
 
-pub fn highlight(lines: &str, extension: &str) -> Option<Vec<Vec<(Style, String)>>> {
-    let syntax = SYNTAX.syntax.find_syntax_by_extension(extension)?;
+/// Highlights the lines of a file, selecting the syntax by the file's path and first line
+pub fn highlight(lines: &str, path: &str) -> Option<Vec<Vec<(Style, String)>>> {
+    let syntax_def = find_syntax(path, lines)?;
 
-    let mut h = HighlightLines::new(syntax, &THEME);
+    let mut h = HighlightLines::new(syntax_def, &syntax().theme);
 
     // TODO: Due to a bug in tui-rs (?), it is necessary to trim line ends.
     // Otherwise, artifacts of the previous buffer may occur
     let spans: Vec<_> = lines
         .lines()
         .map(|line| {
-            h.highlight_line(line.trim_end(), &SYNTAX.syntax)
+            h.highlight_line(line.trim_end(), &syntax().syntax)
                 .unwrap()
                 .into_iter()
                 .map(|(s, l)| (s, l.to_string()))
@@ -55,6 +132,78 @@ pub fn highlight(lines: &str, extension: &str) -> Option<Vec<Vec<(Style, String)
     Some(spans)
 }
 
+/// Maximum number of files kept in a `HighlightCache`
+const MAX_CACHED_FILES: usize = 64;
+
+/// A file that is highlighted line by line, on demand.
+/// Lines are highlighted in order, as the highlighter's state carries over to the next line,
+/// but never beyond the last requested line.
+pub struct HighlightedFile {
+    lines: Vec<String>,
+    highlighted: Vec<Vec<(Style, String)>>,
+    highlighter: HighlightLines<'static>,
+}
+
+impl HighlightedFile {
+    pub fn new(text: &str, path: &str) -> Option<Self> {
+        let syntax_def = find_syntax(path, text)?;
+        Some(HighlightedFile {
+            lines: text
+                .lines()
+                .map(|line| line.trim_end_matches('\r').to_string())
+                .collect(),
+            highlighted: vec![],
+            highlighter: HighlightLines::new(syntax_def, &syntax().theme),
+        })
+    }
+
+    /// The highlighted line with the given (1-based) line number
+    pub fn line(&mut self, number: usize) -> Option<&[(Style, String)]> {
+        let end = number.min(self.lines.len());
+        for line in &self.lines[self.highlighted.len().min(end)..end] {
+            let spans = self
+                .highlighter
+                .highlight_line(line, &syntax().syntax)
+                .ok()?
+                .into_iter()
+                .map(|(s, l)| (s, l.to_string()))
+                .collect();
+            self.highlighted.push(spans);
+        }
+        number
+            .checked_sub(1)
+            .and_then(|idx| self.highlighted.get(idx))
+            .map(|line| &line[..])
+    }
+}
+
+/// Highlighted files by blob and path, so that only new lines are highlighted
+/// when moving between commits or changing the number of context lines
+#[derive(Default)]
+pub struct HighlightCache {
+    files: HashMap<(Oid, String), HighlightedFile>,
+}
+
+impl HighlightCache {
+    /// The highlighted file for a blob, created from `text` if not cached yet
+    pub fn get(
+        &mut self,
+        oid: Oid,
+        path: &str,
+        text: impl FnOnce() -> Option<String>,
+    ) -> Option<&mut HighlightedFile> {
+        let key = (oid, path.to_string());
+        if !self.files.contains_key(&key) {
+            let file = HighlightedFile::new(&text()?, path)?;
+            if self.files.len() >= MAX_CACHED_FILES {
+                self.files.clear();
+            }
+            self.files.insert(key.clone(), file);
+        }
+        self.files.get_mut(&key)
+    }
+}
+
 pub fn as_styled(lines: &'_ [Vec<(Style, String)>]) -> Text<'_> {
     let spans: Vec<_> = lines
         .iter()
@@ -0,0 +1,151 @@
+use git2::Repository;
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+/// Time without further changes before changes are reported
+const DEBOUNCE_TIME: Duration = Duration::from_millis(300);
+
+/// Parts of a repository that changed
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct Changes {
+    /// References, i.e. branches, tags, remotes, stashes or HEAD
+    pub refs: bool,
+    /// The index, i.e. staged changes
+    pub index: bool,
+    /// Files in the working tree, except ignored ones
+    pub worktree: bool,
+}
+
+impl Changes {
+    pub fn any(&self) -> bool {
+        self.refs || self.index || self.worktree
+    }
+
+    fn merge(&mut self, other: Changes) {
+        self.refs |= other.refs;
+        self.index |= other.index;
+        self.worktree |= other.worktree;
+    }
+}
+
+/// Watches a repository's references (`refs`, `packed-refs`, `HEAD`), index and working tree
+/// for changes.
+pub struct RepoWatcher {
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<Changes>,
+    pending: Changes,
+    last_change: Option<Instant>,
+    watches_worktree: bool,
+}
+
+impl RepoWatcher {
+    /// Starts watching the repository with the given `.git` directory and working tree.
+    /// Fails if watching is not supported, e.g. on some network file systems.
+    /// If only the working tree can't be watched, e.g. due to the limit of watched
+    /// directories, it must be checked by the caller (see `watches_worktree`).
+    pub fn new(git_dir: &Path, workdir: Option<&Path>) -> Result<Self, String> {
+        let git_dir = git_dir.canonicalize().map_err(|err| err.to_string())?;
+        let workdir = workdir.and_then(|dir| dir.canonicalize().ok());
+        let (sender, receiver) = channel();
+
+        let root = git_dir.clone();
+        let work_root = workdir.clone();
+        // Opened by the watcher thread, to skip ignored files
+        let mut repository: Option<Repository> = None;
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                if let Ok(event) = event {
+                    let mutating = !matches!(
+                        event.kind,
+                        EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_))
+                    );
+                    if mutating {
+                        if repository.is_none() && work_root.is_some() {
+                            repository = Repository::open(&root).ok();
+                        }
+                        let changes = classify(
+                            &root,
+                            work_root.as_deref(),
+                            repository.as_ref(),
+                            &event.paths,
+                        );
+                        if changes.any() {
+                            let _ = sender.send(changes);
+                        }
+                    }
+                }
+            })
+            .map_err(|err| err.to_string())?;
+
+        watcher
+            .watch(&git_dir, RecursiveMode::NonRecursive)
+            .map_err(|err| err.to_string())?;
+        watcher
+            .watch(&git_dir.join("refs"), RecursiveMode::Recursive)
+            .map_err(|err| err.to_string())?;
+        let watches_worktree = workdir
+            .map(|dir| watcher.watch(&dir, RecursiveMode::Recursive).is_ok())
+            .unwrap_or(false);
+
+        Ok(RepoWatcher {
+            _watcher: watcher,
+            receiver,
+            pending: Changes::default(),
+            last_change: None,
+            watches_worktree,
+        })
+    }
+
+    /// Whether changes in the working tree are reported
+    pub fn watches_worktree(&self) -> bool {
+        self.watches_worktree
+    }
+
+    /// The changes since the last call, once no further changes happened for `DEBOUNCE_TIME`
+    pub fn poll(&mut self) -> Option<Changes> {
+        let now = Instant::now();
+        while let Ok(changes) = self.receiver.try_recv() {
+            self.pending.merge(changes);
+            self.last_change = Some(now);
+        }
+        match self.last_change {
+            Some(last) if now.saturating_duration_since(last) >= DEBOUNCE_TIME => {
+                self.last_change = None;
+                Some(std::mem::take(&mut self.pending))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn classify(
+    git_dir: &Path,
+    workdir: Option<&Path>,
+    repository: Option<&Repository>,
+    paths: &[PathBuf],
+) -> Changes {
+    let mut changes = Changes::default();
+    for path in paths {
+        if let Ok(relative) = path.strip_prefix(git_dir) {
+            if relative.starts_with("refs")
+                || relative == Path::new("packed-refs")
+                || relative == Path::new("HEAD")
+            {
+                changes.refs = true;
+            } else if relative == Path::new("index") {
+                changes.index = true;
+            }
+        } else if let Some(relative) = workdir.and_then(|dir| path.strip_prefix(dir).ok()) {
+            let ignored = repository
+                .and_then(|repo| repo.is_path_ignored(relative).ok())
+                .unwrap_or(false);
+            if !ignored {
+                changes.worktree = true;
+            }
+        }
+    }
+    changes
+}
@@ -1,4 +1,5 @@
 use crate::widgets::list::{ListItem, ListState};
+use git2::Oid;
 use tui::buffer::Buffer;
 use tui::layout::{Corner, Rect};
 use tui::style::Style;
@@ -14,6 +15,11 @@ pub enum BranchItemType {
     LocalBranch,
     RemoteBranch,
     Tag,
+    /// Stash with its index, as in `stash@{index}`, and its commit
+    Stash {
+        index: usize,
+        oid: Oid,
+    },
     Heading,
 }
 
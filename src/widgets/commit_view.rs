@@ -1,6 +1,8 @@
 use crate::app::DiffType;
 use crate::util::ctrl_chars::CtrlChars;
-use crate::widgets::list::{ListItem, StatefulList};
+use crate::widgets::file_tree::{FileTree, TreeRow};
+use crate::widgets::graph_view::PseudoCommit;
+use crate::widgets::list::{ListItem, ListState, StatefulList};
 use git2::Oid;
 use tui::buffer::Buffer;
 use tui::layout::Rect;
@@ -26,8 +28,12 @@ impl ListItem for DiffItem {
 pub struct CommitViewInfo {
     pub text: Vec<String>,
     pub diffs: StatefulList<DiffItem>,
+    /// The changed files grouped by directory, shown in the file tree mode
+    pub tree: FileTree,
     pub oid: Oid,
     pub compare_oid: Oid,
+    /// Uncommitted changes shown instead of a commit
+    pub pseudo: Option<PseudoCommit>,
     pub scroll: u16,
 }
 impl CommitViewInfo {
@@ -40,11 +46,51 @@ impl CommitViewInfo {
         Self {
             text,
             diffs,
+            tree: FileTree::default(),
             oid,
             compare_oid,
+            pseudo: None,
             scroll: 0,
         }
     }
+
+    /// Updates the file tree to the changed files and the selected file
+    pub fn sync_tree(&mut self) {
+        self.tree
+            .sync(&self.diffs.items, self.diffs.state.selected());
+    }
+
+    /// Selects the file of the selected tree row, or no file for a directory.
+    /// Returns whether the selected file changed.
+    pub fn select_tree_file(&mut self) -> bool {
+        let file = self.tree.selected_file();
+        let changed = self.diffs.state.selected() != file;
+        self.diffs.state.select(file);
+        changed
+    }
+
+    /// Index of the first file in the files list, or in the file tree
+    pub fn first_file(&mut self, tree: bool) -> usize {
+        if tree {
+            self.sync_tree();
+            self.tree.rows.iter().find_map(|row| match row {
+                TreeRow::File { index, .. } => Some(*index),
+                _ => None,
+            })
+        } else {
+            None
+        }
+        .unwrap_or(0)
+    }
+
+    /// The state of the files list, or of the file tree
+    pub fn files_state(&mut self, tree: bool) -> &mut ListState {
+        if tree {
+            &mut self.tree.state
+        } else {
+            &mut self.diffs.state
+        }
+    }
 }
 
 #[derive(Default)]
@@ -1,17 +1,55 @@
+use crate::util::diff::{word_changes, ChangedRanges};
+use crate::util::syntax_highlight::HighlightCache;
+use crate::widgets::graph_view::PseudoCommit;
 use git2::Oid;
 use syntect::highlighting::Style;
 
+/// Blame annotations of the file shown in old/new mode
+pub struct BlameInfo {
+    /// Commit and annotation of each line of the file
+    pub lines: Vec<(Oid, String)>,
+    /// Number of header lines shown above the file's content
+    pub header_lines: usize,
+    /// Selected line of the file
+    pub cursor: usize,
+}
+
+impl BlameInfo {
+    pub fn move_cursor(&mut self, steps: usize, down: bool) {
+        self.cursor = if down {
+            (self.cursor + steps).min(self.lines.len().saturating_sub(1))
+        } else {
+            self.cursor.saturating_sub(steps)
+        };
+    }
+
+    pub fn selected_oid(&self) -> Option<Oid> {
+        self.lines.get(self.cursor).map(|(oid, _)| *oid)
+    }
+}
+
 #[derive(Default)]
 pub struct DiffViewState {
     pub content: Option<DiffViewInfo>,
+    pub highlight_cache: HighlightCache,
 }
 
 pub struct DiffViewInfo {
     pub diffs: Vec<(String, Option<u32>, Option<u32>)>,
+    /// Syntax highlighting of the file in old/new mode, or of each line in diff modes
+    /// (without the leading `+`/`-`, and empty for lines that are not highlighted)
     pub highlighted: Option<Vec<Vec<(Style, String)>>>,
+    /// Changed byte ranges of each line, for word-level highlighting
+    pub changes: Vec<ChangedRanges>,
     pub oid: Oid,
     pub compare_oid: Oid,
+    /// Uncommitted changes shown instead of a commit
+    pub pseudo: Option<PseudoCommit>,
     pub scroll: (u16, u16),
+    pub search_line: Option<usize>,
+    /// Whether the next draw scrolls to the search match, as its row depends on line wrapping
+    pub scroll_to_search: bool,
+    pub blame: Option<BlameInfo>,
 }
 impl DiffViewInfo {
     pub fn new(
@@ -21,11 +59,16 @@ impl DiffViewInfo {
         compare_oid: Oid,
     ) -> Self {
         Self {
+            changes: word_changes(&diffs),
             diffs,
             highlighted,
             oid,
             compare_oid,
+            pseudo: None,
             scroll: (0, 0),
+            search_line: None,
+            scroll_to_search: false,
+            blame: None,
         }
     }
 }
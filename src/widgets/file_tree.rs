@@ -0,0 +1,303 @@
+use crate::widgets::commit_view::DiffItem;
+use crate::widgets::list::ListState;
+use std::collections::{BTreeMap, HashSet};
+
+/// A row of the file tree, either a directory or a changed file
+pub enum TreeRow {
+    Dir {
+        /// Full path of the directory
+        path: String,
+        /// Displayed name. Directories with a single subdirectory and no files are merged, like `src/widgets`.
+        name: String,
+        depth: usize,
+        /// Number of changed files in the directory and its subdirectories
+        files: usize,
+        collapsed: bool,
+    },
+    File {
+        /// Index into the flat list of changed files
+        index: usize,
+        depth: usize,
+    },
+}
+
+/// The changed files of a commit, grouped by directory.
+/// The rows are derived from the flat file list, which keeps track of the selected file.
+#[derive(Default)]
+pub struct FileTree {
+    pub rows: Vec<TreeRow>,
+    pub state: ListState,
+    /// Paths of collapsed directories
+    pub collapsed: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Node {
+    dirs: BTreeMap<String, Node>,
+    files: Vec<usize>,
+}
+
+impl Node {
+    fn count(&self) -> usize {
+        self.files.len() + self.dirs.values().map(Node::count).sum::<usize>()
+    }
+}
+
+impl FileTree {
+    /// Rebuilds the rows from the changed files
+    pub fn update(&mut self, files: &[DiffItem]) {
+        let mut root = Node::default();
+        for (index, item) in files.iter().enumerate() {
+            let mut node = &mut root;
+            if let Some((dir, _)) = item.file.rsplit_once('/') {
+                for part in dir.split('/') {
+                    node = node.dirs.entry(part.to_string()).or_default();
+                }
+            }
+            node.files.push(index);
+        }
+        self.rows.clear();
+        self.add_rows(&root, "", 0);
+
+        if let Some(sel) = self.state.selected() {
+            if sel >= self.rows.len() {
+                self.state.select(self.rows.len().checked_sub(1));
+            }
+        }
+    }
+
+    fn add_rows(&mut self, node: &Node, parent: &str, depth: usize) {
+        for (name, child) in &node.dirs {
+            let mut name = name.clone();
+            let mut child = child;
+            while child.files.is_empty() && child.dirs.len() == 1 {
+                let (sub_name, sub_node) = child.dirs.iter().next().unwrap();
+                name = format!("{}/{}", name, sub_name);
+                child = sub_node;
+            }
+            let path = if parent.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", parent, name)
+            };
+            let collapsed = self.collapsed.contains(&path);
+            self.rows.push(TreeRow::Dir {
+                path: path.clone(),
+                name,
+                depth,
+                files: child.count(),
+                collapsed,
+            });
+            if !collapsed {
+                self.add_rows(child, &path, depth + 1);
+            }
+        }
+        for index in &node.files {
+            self.rows.push(TreeRow::File {
+                index: *index,
+                depth,
+            });
+        }
+    }
+
+    /// Index of the file in the selected row, `None` if a directory is selected
+    pub fn selected_file(&self) -> Option<usize> {
+        match self.state.selected().and_then(|sel| self.rows.get(sel)) {
+            Some(TreeRow::File { index, .. }) => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// Rebuilds the rows and moves the selection to the given file,
+    /// expanding the directories containing it
+    pub fn sync(&mut self, files: &[DiffItem], selected: Option<usize>) {
+        self.update(files);
+        match selected.and_then(|index| files.get(index).map(|item| (index, item))) {
+            Some((index, item)) if self.selected_file() != Some(index) => {
+                self.collapsed
+                    .retain(|dir| !item.file.starts_with(&format!("{}/", dir)));
+                self.update(files);
+                let row = self
+                    .rows
+                    .iter()
+                    .position(|row| matches!(row, TreeRow::File { index: i, .. } if *i == index));
+                self.state.select(row);
+            }
+            None if self.selected_file().is_some() => self.state.select(None),
+            _ => {}
+        }
+    }
+
+    pub fn fwd(&mut self, steps: usize) -> bool {
+        if self.rows.is_empty() {
+            return false;
+        }
+        let old = self.state.selected();
+        let new = match old {
+            Some(sel) => (sel + steps).min(self.rows.len() - 1),
+            None => 0,
+        };
+        self.state.select(Some(new));
+        old != Some(new)
+    }
+
+    pub fn bwd(&mut self, steps: usize) -> bool {
+        if self.rows.is_empty() {
+            return false;
+        }
+        let old = self.state.selected();
+        let new = match old {
+            Some(sel) => sel.saturating_sub(steps),
+            None => 0,
+        };
+        self.state.select(Some(new));
+        old != Some(new)
+    }
+
+    /// Expands or collapses the selected directory.
+    /// For a selected file, collapses the containing directory and selects it.
+    pub fn toggle(&mut self, files: &[DiffItem]) {
+        let sel = match self.state.selected() {
+            Some(sel) if sel < self.rows.len() => sel,
+            _ => return,
+        };
+        let dir_row = match &self.rows[sel] {
+            TreeRow::Dir { .. } => Some(sel),
+            TreeRow::File { depth, .. } => self.rows[..sel]
+                .iter()
+                .rposition(|row| matches!(row, TreeRow::Dir { depth: d, .. } if d < depth)),
+        };
+        if let Some(TreeRow::Dir {
+            path, collapsed, ..
+        }) = dir_row.map(|row| &self.rows[row])
+        {
+            if *collapsed {
+                self.collapsed.remove(path);
+            } else {
+                self.collapsed.insert(path.clone());
+            }
+            self.state.select(dir_row);
+            self.update(files);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::DiffType;
+
+    fn files(paths: &[&str]) -> Vec<DiffItem> {
+        paths
+            .iter()
+            .map(|path| DiffItem {
+                file: path.to_string(),
+                diff_type: DiffType::Modified,
+            })
+            .collect()
+    }
+
+    /// Rows as `name/` for directories and the file index otherwise, indented by depth
+    fn rows(tree: &FileTree) -> Vec<String> {
+        tree.rows
+            .iter()
+            .map(|row| match row {
+                TreeRow::Dir {
+                    name,
+                    depth,
+                    collapsed,
+                    files,
+                    ..
+                } => format!(
+                    "{}{}/ {}{}",
+                    "  ".repeat(*depth),
+                    name,
+                    files,
+                    if *collapsed { " +" } else { "" }
+                ),
+                TreeRow::File { index, depth } => format!("{}{}", "  ".repeat(*depth), index),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_single_child_directories() {
+        let files = files(&[
+            "src/widgets/list.rs",
+            "src/widgets/file_tree.rs",
+            "src/main.rs",
+            "docs/a/b/c.md",
+            "README.md",
+        ]);
+        let mut tree = FileTree::default();
+        tree.update(&files);
+        assert_eq!(
+            rows(&tree),
+            vec![
+                "docs/a/b/ 1",
+                "  3",
+                "src/ 3",
+                "  widgets/ 2",
+                "    0",
+                "    1",
+                "  2",
+                "4"
+            ]
+        );
+    }
+
+    #[test]
+    fn toggles_directories() {
+        let files = files(&["src/widgets/list.rs", "src/main.rs", "README.md"]);
+        let mut tree = FileTree::default();
+        tree.update(&files);
+        assert_eq!(
+            rows(&tree),
+            vec!["src/ 2", "  widgets/ 1", "    0", "  1", "2"]
+        );
+
+        // Collapsing from a file selects the containing directory
+        tree.state.select(Some(2));
+        tree.toggle(&files);
+        assert_eq!(tree.state.selected(), Some(1));
+        assert_eq!(rows(&tree), vec!["src/ 2", "  widgets/ 1 +", "  1", "2"]);
+
+        tree.state.select(Some(0));
+        tree.toggle(&files);
+        assert_eq!(rows(&tree), vec!["src/ 2 +", "2"]);
+        assert_eq!(tree.selected_file(), None);
+
+        tree.toggle(&files);
+        assert_eq!(rows(&tree), vec!["src/ 2", "  widgets/ 1 +", "  1", "2"]);
+    }
+
+    #[test]
+    fn sync_expands_to_the_selected_file() {
+        let files = files(&["src/widgets/list.rs", "src/main.rs", "README.md"]);
+        let mut tree = FileTree::default();
+        tree.collapsed.insert("src".to_string());
+        tree.collapsed.insert("src/widgets".to_string());
+        tree.sync(&files, Some(2));
+        assert_eq!(rows(&tree), vec!["src/ 2 +", "2"]);
+        assert_eq!(tree.selected_file(), Some(2));
+
+        tree.sync(&files, Some(0));
+        assert!(tree.collapsed.is_empty());
+        assert_eq!(tree.selected_file(), Some(0));
+        assert_eq!(tree.state.selected(), Some(2));
+
+        tree.sync(&files, None);
+        assert_eq!(tree.state.selected(), None);
+    }
+
+    #[test]
+    fn update_clamps_the_selection() {
+        let mut tree = FileTree::default();
+        tree.update(&files(&["a.rs", "b.rs", "c.rs"]));
+        tree.state.select(Some(2));
+        tree.update(&files(&["a.rs"]));
+        assert_eq!(tree.state.selected(), Some(0));
+        tree.update(&[]);
+        assert_eq!(tree.state.selected(), None);
+    }
+}
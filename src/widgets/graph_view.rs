@@ -1,6 +1,7 @@
 use crate::util::ctrl_chars::CtrlChars;
 use crate::widgets::branches_view::BranchItem;
 use crate::widgets::list::StatefulList;
+use git2::Oid;
 use git_graph::graph::GitGraph;
 use std::iter::Iterator;
 use tui::buffer::Buffer;
@@ -12,12 +13,49 @@ use unicode_width::UnicodeWidthStr;
 const SCROLL_MARGIN: usize = 3;
 const SCROLLBAR_STR: &str = "\u{2588}";
 
+/// Uncommitted changes, shown as pseudo-commits above HEAD.
+/// Stashes are shown in the same way when selected in the branch list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PseudoCommit {
+    /// Changes in the index, compared to HEAD
+    Staged,
+    /// Changes in the working tree, compared to the index
+    Unstaged,
+    /// Stash with the given index, compared to the commit it was created on
+    Stash(usize),
+}
+
+impl PseudoCommit {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PseudoCommit::Staged => "Staged changes",
+            PseudoCommit::Unstaged => "Unstaged changes",
+            PseudoCommit::Stash(_) => "Stash",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            PseudoCommit::Staged => "Changes in the index, compared to HEAD",
+            PseudoCommit::Unstaged => "Changes in the working tree, compared to the index",
+            PseudoCommit::Stash(_) => "Stashed changes, compared to the commit they are based on",
+        }
+    }
+}
+
+/// State of the graph panel.
+///
+/// Rows (`selected`, `secondary_selected`, `indices`) start with the `pseudo_commits`,
+/// followed by the commits of the `graph`.
 #[derive(Default)]
 pub struct GraphViewState {
     pub graph: Option<GitGraph>,
     pub graph_lines: Vec<String>,
     pub text_lines: Vec<String>,
     pub indices: Vec<usize>,
+    pub pseudo_commits: Vec<PseudoCommit>,
+    /// Whether each row matches the commit filter, or `None` if no filter is set
+    pub matches: Option<Vec<bool>>,
     pub offset: usize,
     pub selected: Option<usize>,
     pub branches: Option<StatefulList<BranchItem>>,
@@ -26,18 +64,86 @@ pub struct GraphViewState {
 }
 
 impl GraphViewState {
-    pub fn move_selection(&mut self, steps: usize, down: bool) -> bool {
-        let changed = if let Some(sel) = self.selected {
-            let new_idx = if down {
-                std::cmp::min(sel.saturating_add(steps), self.indices.len() - 1)
+    /// Index of the commit in the graph for a row, or `None` for pseudo-commits
+    pub fn commit_index(&self, row: usize) -> Option<usize> {
+        row.checked_sub(self.pseudo_commits.len())
+    }
+
+    /// Row of the commit with the given index in the graph
+    pub fn row_index(&self, commit_index: usize) -> usize {
+        commit_index + self.pseudo_commits.len()
+    }
+
+    pub fn selected_commit(&self) -> Option<usize> {
+        self.selected.and_then(|row| self.commit_index(row))
+    }
+
+    pub fn selected_pseudo(&self) -> Option<PseudoCommit> {
+        self.selected
+            .and_then(|row| self.pseudo_commits.get(row))
+            .cloned()
+    }
+
+    pub fn secondary_selected_commit(&self) -> Option<usize> {
+        self.secondary_selected
+            .and_then(|row| self.commit_index(row))
+    }
+
+    /// Row of the commit with the given id, if it is in the graph
+    pub fn row_of_oid(&self, oid: Option<Oid>) -> Option<usize> {
+        let graph = self.graph.as_ref()?;
+        oid.and_then(|oid| graph.indices.get(&oid))
+            .map(|idx| self.row_index(*idx))
+    }
+
+    pub fn is_match(&self, row: usize) -> bool {
+        self.matches
+            .as_ref()
+            .map(|matches| matches[row])
+            .unwrap_or(true)
+    }
+
+    /// Number of commits matching the filter
+    pub fn num_matches(&self) -> usize {
+        self.matches
+            .as_ref()
+            .map(|matches| matches.iter().filter(|m| **m).count())
+            .unwrap_or(self.indices.len() - self.pseudo_commits.len())
+    }
+
+    /// Moves up to `steps` rows from `row`, skipping rows not matching the filter
+    fn step_rows(&self, row: usize, steps: usize, down: bool) -> usize {
+        let mut result = row;
+        let mut remaining = steps;
+        let mut current = row;
+        while remaining > 0 {
+            current = if down {
+                if current + 1 >= self.indices.len() {
+                    break;
+                }
+                current + 1
             } else {
-                std::cmp::max(sel.saturating_sub(steps), 0)
+                match current.checked_sub(1) {
+                    Some(prev) => prev,
+                    None => break,
+                }
             };
+            if self.is_match(current) {
+                result = current;
+                remaining -= 1;
+            }
+        }
+        result
+    }
+
+    pub fn move_selection(&mut self, steps: usize, down: bool) -> bool {
+        let changed = if let Some(sel) = self.selected {
+            let new_idx = self.step_rows(sel, steps, down);
             self.selected = Some(new_idx);
             new_idx != sel
         } else if !self.graph_lines.is_empty() {
-            self.selected = Some(0);
-            true
+            self.selected = (0..self.indices.len()).find(|row| self.is_match(*row));
+            self.selected.is_some()
         } else {
             false
         };
@@ -48,20 +154,12 @@ impl GraphViewState {
     }
     pub fn move_secondary_selection(&mut self, steps: usize, down: bool) -> bool {
         let changed = if let Some(sel) = self.secondary_selected {
-            let new_idx = if down {
-                std::cmp::min(sel.saturating_add(steps), self.indices.len() - 1)
-            } else {
-                std::cmp::max(sel.saturating_sub(steps), 0)
-            };
+            let new_idx = self.step_rows(sel, steps, down);
             self.secondary_selected = Some(new_idx);
             new_idx != sel
         } else if !self.graph_lines.is_empty() {
             if let Some(sel) = self.selected {
-                let new_idx = if down {
-                    std::cmp::min(sel.saturating_add(steps), self.indices.len() - 1)
-                } else {
-                    std::cmp::max(sel.saturating_sub(steps), 0)
-                };
+                let new_idx = self.step_rows(sel, steps, down);
                 self.secondary_selected = Some(new_idx);
                 new_idx != sel
             } else {
@@ -84,6 +182,7 @@ pub struct GraphView<'a> {
     secondary_highlight_symbol: Option<&'a str>,
     style: Style,
     highlight_style: Style,
+    filtered_style: Style,
 }
 
 impl<'a> GraphView<'a> {
@@ -111,6 +210,12 @@ impl<'a> GraphView<'a> {
         self.highlight_style = style;
         self
     }
+
+    /// Style for the text of rows not matching the filter
+    pub fn filtered_style(mut self, style: Style) -> GraphView<'a> {
+        self.filtered_style = style;
+        self
+    }
 }
 
 impl StatefulWidget for GraphView<'_> {
@@ -224,23 +329,43 @@ impl StatefulWidget for GraphView<'_> {
 
             let max_element_width = (list_area.width - (elem_x - x)) as usize;
 
-            let mut body = CtrlChars::parse(graph_item).into_text();
-            body.extend(CtrlChars::parse(&format!("  {}", text_item)).into_text());
+            let graph_text = CtrlChars::parse(graph_item).into_text();
+            let text = CtrlChars::parse(&format!("  {}", text_item)).into_text();
 
             let mut x = elem_x;
             let mut remaining_width = max_element_width as u16;
-            for txt in body {
-                for line in txt.lines {
-                    if remaining_width == 0 {
-                        break;
+            let mut text_x = x;
+            for (is_text, body) in [(false, graph_text), (true, text)] {
+                if is_text {
+                    text_x = x;
+                }
+                for txt in body {
+                    for line in txt.lines {
+                        if remaining_width == 0 {
+                            break;
+                        }
+                        let pos = buf.set_spans(x, y, &line, remaining_width);
+                        let w = pos.0.saturating_sub(x);
+                        x = pos.0;
+                        remaining_width = remaining_width.saturating_sub(w);
                     }
-                    let pos = buf.set_spans(x, y, &line, remaining_width);
-                    let w = pos.0.saturating_sub(x);
-                    x = pos.0;
-                    remaining_width = remaining_width.saturating_sub(w);
                 }
             }
 
+            let row = state
+                .indices
+                .partition_point(|line| *line <= i)
+                .saturating_sub(1);
+            if !state.is_match(row) {
+                let text_area = Rect {
+                    x: text_x,
+                    y,
+                    width: x.saturating_sub(text_x),
+                    height: 1,
+                };
+                buf.set_style(text_area, self.filtered_style);
+            }
+
             if is_selected || is_sec_selected {
                 buf.set_style(area, self.highlight_style);
             }
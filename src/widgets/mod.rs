@@ -1,7 +1,9 @@
 pub mod branches_view;
 pub mod commit_view;
 pub mod diff_view;
+pub mod file_tree;
 pub mod files_view;
 pub mod graph_view;
 pub mod list;
 pub mod models_view;
+pub mod reflog_view;
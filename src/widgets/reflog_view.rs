@@ -0,0 +1,44 @@
+use crate::widgets::list::{ListItem, StatefulList};
+use git2::Oid;
+
+/// An entry of a reflog, i.e. a change of a reference from one commit to another
+pub struct ReflogItem {
+    /// The entry's name, like `HEAD@{2}`
+    pub selector: String,
+    pub old: Oid,
+    pub new: Oid,
+    pub message: String,
+    pub time: String,
+}
+
+impl ListItem for ReflogItem {
+    fn is_selectable(&self) -> bool {
+        true
+    }
+}
+
+/// The reflog of a reference, shown in place of the graph.
+/// Entries may point to commits that are no longer reachable from any reference.
+pub struct ReflogState {
+    /// The reference, like `HEAD` or `refs/heads/main`
+    pub name: String,
+    pub entries: StatefulList<ReflogItem>,
+}
+
+impl ReflogState {
+    pub fn new(name: String, entries: Vec<ReflogItem>) -> Self {
+        ReflogState {
+            name,
+            entries: StatefulList::with_items(entries),
+        }
+    }
+
+    /// The commit of the selected entry
+    pub fn selected_oid(&self) -> Option<Oid> {
+        self.entries
+            .state
+            .selected()
+            .and_then(|idx| self.entries.items.get(idx))
+            .map(|item| item.new)
+    }
+}